@@ -0,0 +1,118 @@
+// src/activity.rs
+
+use crate::ADB;
+use std::io;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActivityInfo {
+    pub resumed_activity: Option<String>,
+    pub focused_window: Option<String>,
+    pub top_package: Option<String>,
+}
+
+impl ActivityInfo {
+    fn parse(activities: &str, windows: &str) -> Self {
+        let resumed_activity = find_component(
+            activities,
+            &["topResumedActivity=", "mResumedActivity:", "ResumedActivity:"],
+        );
+        let focused_window = find_component(windows, &["mCurrentFocus=", "mFocusedWindow="])
+            .or_else(|| find_component(windows, &["mFocusedApp="]));
+        let top_package = resumed_activity
+            .as_deref()
+            .or(focused_window.as_deref())
+            .and_then(|component| component.split('/').next())
+            .map(str::to_string);
+
+        ActivityInfo {
+            resumed_activity,
+            focused_window,
+            top_package,
+        }
+    }
+
+    // Accepts either a bare package name or a `package/activity` component,
+    // with the activity optionally in the `.Short` form.
+    pub fn matches(&self, component: &str) -> bool {
+        if !component.contains('/') {
+            return self.top_package.as_deref() == Some(component);
+        }
+        let expected = expand_component(component);
+        self.resumed_activity.as_deref() == Some(expected.as_str())
+            || self.focused_window.as_deref() == Some(expected.as_str())
+    }
+}
+
+fn expand_component(component: &str) -> String {
+    match component.split_once('/') {
+        Some((package, activity)) if activity.starts_with('.') => format!("{}/{}{}", package, package, activity),
+        _ => component.to_string(),
+    }
+}
+
+fn find_component(dump: &str, markers: &[&str]) -> Option<String> {
+    dump.lines().find_map(|line| {
+        let line = line.trim();
+        let rest = markers.iter().find_map(|marker| line.strip_prefix(marker))?;
+        rest.split_whitespace()
+            .map(|token| token.trim_end_matches('}'))
+            .find(|token| token.contains('/'))
+            .map(expand_component)
+    })
+}
+
+fn timed_out(component: &str, timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("Activity {} not shown within {:?}", component, timeout),
+    )
+}
+
+impl ADB {
+    pub fn get_current_activity(&self, device: &str) -> Result<ActivityInfo, io::Error> {
+        let activities = self.run_adb(&format!("{} shell dumpsys activity activities", device))?;
+        let windows = self.run_adb(&format!("{} shell dumpsys window windows", device))?;
+        Ok(ActivityInfo::parse(&activities, &windows))
+    }
+
+    pub async fn get_current_activity_async(&self, device: &str) -> Result<ActivityInfo, io::Error> {
+        let activities = self
+            .run_adb_async(&format!("{} shell dumpsys activity activities", device))
+            .await?;
+        let windows = self
+            .run_adb_async(&format!("{} shell dumpsys window windows", device))
+            .await?;
+        Ok(ActivityInfo::parse(&activities, &windows))
+    }
+
+    pub fn wait_for_activity(&self, device: &str, component: &str, timeout: Duration) -> Result<ActivityInfo, io::Error> {
+        let start = Instant::now();
+        loop {
+            let info = self.get_current_activity(device)?;
+            if info.matches(component) {
+                return Ok(info);
+            }
+            if start.elapsed() >= timeout {
+                return Err(timed_out(component, timeout));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    pub async fn wait_for_activity_async(&self, device: &str, component: &str, timeout: Duration) -> Result<ActivityInfo, io::Error> {
+        let start = Instant::now();
+        loop {
+            let info = self.get_current_activity_async(device).await?;
+            if info.matches(component) {
+                return Ok(info);
+            }
+            if start.elapsed() >= timeout {
+                return Err(timed_out(component, timeout));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// src/alarms.rs
+
+use crate::dumpsys::parse_duration;
+use crate::ADB;
+use std::io;
+use std::time::Duration;
+
+const ALARM_TYPES: [&str; 4] = ["RTC_WAKEUP", "RTC", "ELAPSED_WAKEUP", "ELAPSED"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AlarmInfo {
+    pub alarm_type: String,
+    pub package: String,
+    pub tag: Option<String>,
+    // Time left until the alarm fires; zero when it is already overdue.
+    pub trigger_in: Option<Duration>,
+    pub repeat_interval: Option<Duration>,
+}
+
+impl AlarmInfo {
+    pub fn is_wakeup(&self) -> bool {
+        self.alarm_type.ends_with("_WAKEUP")
+    }
+
+    pub fn is_repeating(&self) -> bool {
+        self.repeat_interval.is_some()
+    }
+}
+
+// Alarm entries in `dumpsys alarm` start with a header line followed by
+// indented details:
+//   RTC_WAKEUP #0: Alarm{6e0c3a type 0 origWhen 1690000000000 com.foo}
+//     tag=*walarm*:com.foo.SYNC
+//     type=RTC_WAKEUP origWhen=2023-07-22 10:00:00.000 window=0 repeatInterval=900000 count=0 flags=0x9
+//     whenElapsed=+14m59s812ms maxWhenElapsed=+14m59s812ms
+fn parse_header(line: &str) -> Option<AlarmInfo> {
+    let (alarm_type, rest) = line.split_once(" #")?;
+    if !ALARM_TYPES.contains(&alarm_type) {
+        return None;
+    }
+    let (_, alarm) = rest.split_once(": Alarm{")?;
+    let package = alarm.trim_end_matches('}').split_whitespace().last()?;
+    Some(AlarmInfo {
+        alarm_type: alarm_type.to_string(),
+        package: package.to_string(),
+        ..AlarmInfo::default()
+    })
+}
+
+fn parse_alarms(output: &str, package_filter: Option<&str>) -> Vec<AlarmInfo> {
+    let mut alarms: Vec<AlarmInfo> = Vec::new();
+    let mut current: Option<AlarmInfo> = None;
+
+    for line in output.lines().map(str::trim) {
+        if let Some(alarm) = parse_header(line) {
+            alarms.extend(current.replace(alarm));
+            continue;
+        }
+        let Some(alarm) = current.as_mut() else {
+            continue;
+        };
+        if let Some(tag) = line.strip_prefix("tag=") {
+            alarm.tag = Some(tag.to_string());
+            continue;
+        }
+        for token in line.split_whitespace() {
+            if let Some(interval) = token.strip_prefix("repeatInterval=") {
+                alarm.repeat_interval = interval
+                    .parse()
+                    .ok()
+                    .filter(|&millis| millis > 0)
+                    .map(Duration::from_millis);
+            } else if let Some(when) = token.strip_prefix("whenElapsed=") {
+                alarm.trigger_in = if when.starts_with('-') {
+                    Some(Duration::ZERO)
+                } else {
+                    parse_duration(when)
+                };
+            }
+        }
+    }
+    alarms.extend(current);
+
+    alarms
+        .into_iter()
+        .filter(|alarm| package_filter.is_none_or(|package| alarm.package == package))
+        .collect()
+}
+
+impl ADB {
+    pub fn list_alarms(&self, device: &str, package_filter: Option<&str>) -> Result<Vec<AlarmInfo>, io::Error> {
+        let output = self.run_adb(&format!("{} shell dumpsys alarm", device))?;
+        Ok(parse_alarms(&output, package_filter))
+    }
+
+    pub async fn list_alarms_async(&self, device: &str, package_filter: Option<&str>) -> Result<Vec<AlarmInfo>, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell dumpsys alarm", device)).await?;
+        Ok(parse_alarms(&output, package_filter))
+    }
+}
@@ -0,0 +1,385 @@
+// src/audio.rs
+
+use crate::ADB;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioStream {
+    VoiceCall,
+    System,
+    Ring,
+    Music,
+    Alarm,
+    Notification,
+    BluetoothSco,
+    SystemEnforced,
+    Dtmf,
+    Tts,
+    Accessibility,
+    Assistant,
+}
+
+impl AudioStream {
+    const ALL: [AudioStream; 12] = [
+        AudioStream::VoiceCall,
+        AudioStream::System,
+        AudioStream::Ring,
+        AudioStream::Music,
+        AudioStream::Alarm,
+        AudioStream::Notification,
+        AudioStream::BluetoothSco,
+        AudioStream::SystemEnforced,
+        AudioStream::Dtmf,
+        AudioStream::Tts,
+        AudioStream::Accessibility,
+        AudioStream::Assistant,
+    ];
+
+    // Matches android.media.AudioManager.STREAM_*.
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    fn dump_name(self) -> &'static str {
+        match self {
+            AudioStream::VoiceCall => "STREAM_VOICE_CALL",
+            AudioStream::System => "STREAM_SYSTEM",
+            AudioStream::Ring => "STREAM_RING",
+            AudioStream::Music => "STREAM_MUSIC",
+            AudioStream::Alarm => "STREAM_ALARM",
+            AudioStream::Notification => "STREAM_NOTIFICATION",
+            AudioStream::BluetoothSco => "STREAM_BLUETOOTH_SCO",
+            AudioStream::SystemEnforced => "STREAM_SYSTEM_ENFORCED",
+            AudioStream::Dtmf => "STREAM_DTMF",
+            AudioStream::Tts => "STREAM_TTS",
+            AudioStream::Accessibility => "STREAM_ACCESSIBILITY",
+            AudioStream::Assistant => "STREAM_ASSISTANT",
+        }
+    }
+
+    fn from_dump_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|stream| stream.dump_name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingerMode {
+    Silent,
+    Vibrate,
+    Normal,
+}
+
+impl RingerMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            RingerMode::Silent => "SILENT",
+            RingerMode::Vibrate => "VIBRATE",
+            RingerMode::Normal => "NORMAL",
+        }
+    }
+
+    // Volume-down steps move towards Silent, volume-up ones towards Normal.
+    fn rank(self) -> u8 {
+        match self {
+            RingerMode::Silent => 0,
+            RingerMode::Vibrate => 1,
+            RingerMode::Normal => 2,
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "SILENT" | "0" => Some(RingerMode::Silent),
+            "VIBRATE" | "1" => Some(RingerMode::Vibrate),
+            "NORMAL" | "2" => Some(RingerMode::Normal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamVolume {
+    pub stream: AudioStream,
+    pub muted: bool,
+    pub min: u32,
+    pub max: u32,
+    pub current: Option<u32>,
+    pub devices: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AudioInfo {
+    pub ringer_mode: Option<RingerMode>,
+    pub output_device: Option<String>,
+    pub streams: Vec<StreamVolume>,
+}
+
+impl AudioInfo {
+    pub fn stream(&self, stream: AudioStream) -> Option<&StreamVolume> {
+        self.streams.iter().find(|volume| volume.stream == stream)
+    }
+
+    // Parses the `Stream volumes` section of `dumpsys audio`:
+    //   - STREAM_MUSIC:
+    //      Muted: false
+    //      Min: 0
+    //      Max: 15
+    //      Current: 2 (speaker): 7, 80 (bt_a2dp): 10, 40000000 (default): 7
+    //      Devices: speaker
+    fn parse(output: &str) -> Self {
+        let mut info = AudioInfo::default();
+        let mut current: Option<(StreamVolume, String)> = None;
+
+        for line in output.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                if let Some((volume, current_line)) = current.take() {
+                    info.streams.push(finish_stream(volume, &current_line));
+                }
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix("- ").and_then(|rest| rest.strip_suffix(':')) {
+                if let Some((volume, current_line)) = current.take() {
+                    info.streams.push(finish_stream(volume, &current_line));
+                }
+                if let Some(stream) = AudioStream::from_dump_name(name) {
+                    current = Some((
+                        StreamVolume {
+                            stream,
+                            muted: false,
+                            min: 0,
+                            max: 0,
+                            current: None,
+                            devices: None,
+                        },
+                        String::new(),
+                    ));
+                }
+                continue;
+            }
+
+            if let Some(mode) = trimmed
+                .strip_prefix("- mode (external) =")
+                .or_else(|| trimmed.strip_prefix("mRingerMode="))
+            {
+                info.ringer_mode = RingerMode::parse(mode).or(info.ringer_mode);
+                continue;
+            }
+
+            let Some((volume, current_line)) = current.as_mut() else {
+                continue;
+            };
+            let Some((key, value)) = trimmed.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "Muted" => volume.muted = value == "true",
+                "Min" => volume.min = value.parse().unwrap_or(0),
+                "Max" => volume.max = value.parse().unwrap_or(0),
+                "streamVolume" => volume.current = value.parse().ok(),
+                "Current" => *current_line = value.to_string(),
+                "Devices" => volume.devices = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        if let Some((volume, current_line)) = current.take() {
+            info.streams.push(finish_stream(volume, &current_line));
+        }
+
+        info.output_device = info
+            .stream(AudioStream::Music)
+            .and_then(|music| music.devices.clone());
+        info
+    }
+}
+
+// Older builds have no `streamVolume:` line, so the index is taken from the
+// `Current:` entry for the stream's active device.
+fn finish_stream(mut volume: StreamVolume, current_line: &str) -> StreamVolume {
+    if volume.current.is_some() {
+        return volume;
+    }
+    let entries: Vec<(&str, u32)> = current_line
+        .split(", ")
+        .filter_map(|entry| {
+            let (_, rest) = entry.split_once(" (")?;
+            let (device, index) = rest.split_once("): ")?;
+            Some((device, index.trim().parse().ok()?))
+        })
+        .collect();
+    volume.current = volume
+        .devices
+        .as_deref()
+        .and_then(|devices| entries.iter().find(|(device, _)| *device == devices))
+        .or(entries.first())
+        .map(|(_, index)| *index);
+    volume
+}
+
+// Enough ring volume steps to cross the whole range on any device.
+const MAX_RINGER_STEPS: usize = 16;
+
+// Ways to step the ring volume, newest first. Stepping below the lowest
+// level moves the ringer to vibrate or silent, as the volume keys do. The
+// volume keys themselves are no use here: they adjust whichever stream is
+// active, usually music.
+fn ring_volume_commands(device: &str, raise: bool) -> [String; 2] {
+    let direction = if raise { "raise" } else { "lower" };
+    let args = format!("volume --stream {} --adjust {}", AudioStream::Ring.id(), direction);
+    [
+        format!("{} shell cmd media_session {}", device, args),
+        format!("{} shell media {}", device, args),
+    ]
+}
+
+fn not_applied(mode: RingerMode) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Ringer mode {} was not applied by the device", mode.as_str()),
+    )
+}
+
+impl ADB {
+    pub fn get_audio_info(&self, device: &str) -> Result<AudioInfo, io::Error> {
+        let output = self.run_adb(&format!("{} shell dumpsys audio", device))?;
+        Ok(AudioInfo::parse(&output))
+    }
+
+    pub async fn get_audio_info_async(&self, device: &str) -> Result<AudioInfo, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell dumpsys audio", device)).await?;
+        Ok(AudioInfo::parse(&output))
+    }
+
+    // `cmd media_session` replaced the `media` tool on Android 9.
+    pub fn set_stream_volume(&self, device: &str, stream: AudioStream, level: u32) -> Result<(), io::Error> {
+        let args = format!("volume --stream {} --set {}", stream.id(), level);
+        if self.run_adb(&format!("{} shell cmd media_session {}", device, args)).is_err() {
+            self.run_adb(&format!("{} shell media {}", device, args))?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_stream_volume_async(&self, device: &str, stream: AudioStream, level: u32) -> Result<(), io::Error> {
+        let args = format!("volume --stream {} --set {}", stream.id(), level);
+        if self
+            .run_adb_async(&format!("{} shell cmd media_session {}", device, args))
+            .await
+            .is_err()
+        {
+            self.run_adb_async(&format!("{} shell media {}", device, args))
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub fn get_ringer_mode(&self, device: &str) -> Result<Option<RingerMode>, io::Error> {
+        Ok(self.get_audio_info(device)?.ringer_mode)
+    }
+
+    pub async fn get_ringer_mode_async(&self, device: &str) -> Result<Option<RingerMode>, io::Error> {
+        Ok(self.get_audio_info_async(device).await?.ringer_mode)
+    }
+
+    fn step_ring_volume(&self, device: &str, raise: bool) -> Result<(), io::Error> {
+        let mut last_error = None;
+        for command in ring_volume_commands(device, raise) {
+            match self.run_adb(&command) {
+                Ok(_) => return Ok(()),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::other("No way to adjust the ring volume")))
+    }
+
+    async fn step_ring_volume_async(&self, device: &str, raise: bool) -> Result<(), io::Error> {
+        let mut last_error = None;
+        for command in ring_volume_commands(device, raise) {
+            match self.run_adb_async(&command).await {
+                Ok(_) => return Ok(()),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::other("No way to adjust the ring volume")))
+    }
+
+    // Tries `cmd audio set-ringer-mode` first. Many builds lack it or ignore
+    // it silently, so the mode is read back from dumpsys, and when it did not
+    // change the ring volume is stepped up or down until it does. Stepping
+    // down to vibrate or silent drops the ring volume, so the level from
+    // before is kept and put back when a later call steps up to normal.
+    pub fn set_ringer_mode(&self, device: &str, mode: RingerMode) -> Result<(), io::Error> {
+        let _ = self.run_adb(&format!("{} shell cmd audio set-ringer-mode {}", device, mode.as_str()));
+        let mut stepped = false;
+        for _ in 0..=MAX_RINGER_STEPS {
+            let info = self.get_audio_info(device)?;
+            let current = info.ringer_mode.ok_or_else(|| not_applied(mode))?;
+            if current == mode {
+                self.restore_ring_volume(device, mode, stepped)?;
+                return Ok(());
+            }
+            self.save_ring_volume(device, &info);
+            self.step_ring_volume(device, current.rank() < mode.rank())?;
+            stepped = true;
+        }
+        Err(not_applied(mode))
+    }
+
+    pub async fn set_ringer_mode_async(&self, device: &str, mode: RingerMode) -> Result<(), io::Error> {
+        let _ = self
+            .run_adb_async(&format!("{} shell cmd audio set-ringer-mode {}", device, mode.as_str()))
+            .await;
+        let mut stepped = false;
+        for _ in 0..=MAX_RINGER_STEPS {
+            let info = self.get_audio_info_async(device).await?;
+            let current = info.ringer_mode.ok_or_else(|| not_applied(mode))?;
+            if current == mode {
+                self.restore_ring_volume_async(device, mode, stepped).await?;
+                return Ok(());
+            }
+            self.save_ring_volume(device, &info);
+            self.step_ring_volume_async(device, current.rank() < mode.rank())
+                .await?;
+            stepped = true;
+        }
+        Err(not_applied(mode))
+    }
+
+    // Only an audible level is worth keeping, and only the first one, since
+    // later steps have already lowered it.
+    fn save_ring_volume(&self, device: &str, info: &AudioInfo) {
+        if info.ringer_mode != Some(RingerMode::Normal) {
+            return;
+        }
+        if let Some(level) = info.stream(AudioStream::Ring).and_then(|ring| ring.current) {
+            if level > 0 {
+                self.ring_volumes.lock().unwrap().entry(device.to_string()).or_insert(level);
+            }
+        }
+    }
+
+    // Setting a ring volume above zero switches the ringer back to normal,
+    // so the saved level can only go back once the ringer is there. When
+    // `set-ringer-mode` got it there, the device restored the level itself.
+    fn restore_ring_volume(&self, device: &str, mode: RingerMode, stepped: bool) -> Result<(), io::Error> {
+        if mode != RingerMode::Normal {
+            return Ok(());
+        }
+        let saved = self.ring_volumes.lock().unwrap().remove(device);
+        match saved.filter(|_| stepped) {
+            Some(level) => self.set_stream_volume(device, AudioStream::Ring, level),
+            None => Ok(()),
+        }
+    }
+
+    async fn restore_ring_volume_async(&self, device: &str, mode: RingerMode, stepped: bool) -> Result<(), io::Error> {
+        if mode != RingerMode::Normal {
+            return Ok(());
+        }
+        let saved = self.ring_volumes.lock().unwrap().remove(device);
+        match saved.filter(|_| stepped) {
+            Some(level) => self.set_stream_volume_async(device, AudioStream::Ring, level).await,
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,1427 @@
+// src/automation.rs
+
+use crate::{ValidationReport, ADB};
+use futures::future::{BoxFuture, FutureExt};
+use serde::de::Error as _;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio_util::sync::CancellationToken;
+
+// A named list of steps run against one device. String fields of every step
+// may use `${name}` placeholders, resolved when the step is about to run:
+//
+// - `${device}` is the device the workflow runs on,
+// - `${env.NAME}` reads an environment variable of this process,
+// - `${steps.<step>.output}` / `${steps.<step>.status}` refer to a step that
+//   already ran,
+// - anything else is looked up in `vars`.
+//
+// `$${` produces a literal `${`, e.g. for device-side shell variables.
+//
+// `setup` runs before `steps` and, like them, stops at the first failure.
+// `on_failure` runs when anything before it failed or the run was cancelled,
+// and `teardown` always runs last. Hook steps run even after an abort or a
+// cancel, and a failing hook step does not stop the ones after it. Their
+// results are named `setup.<step>`, `on_failure.<step>` and
+// `teardown.<step>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Workflow {
+    pub name: String,
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub setup: Vec<WorkflowStep>,
+    pub steps: Vec<WorkflowStep>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_failure: Vec<WorkflowStep>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teardown: Vec<WorkflowStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    pub name: String,
+    #[serde(flatten)]
+    pub action: StepAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<StepCondition>,
+    // Keeps the workflow going when this step fails.
+    #[serde(default)]
+    pub continue_on_error: bool,
+    // Extra attempts after the first one fails.
+    #[serde(default)]
+    pub retries: u32,
+    #[serde(default)]
+    pub retry_delay_ms: u64,
+    // Applies to each attempt; the command is killed when it runs over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+// Any `type` that is not built in parses as `Custom`, to be run by a handler
+// registered with `WorkflowEngine::register_step_type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", remote = "Self")]
+pub enum StepAction {
+    Shell { command: String },
+    // Any adb subcommand, run against the workflow's device.
+    Adb { args: String },
+    Install { apk: String },
+    Uninstall { package: String },
+    Push { local: String, remote: String },
+    Pull { remote: String, local: String },
+    Tap { x: u32, y: u32 },
+    KeyEvent { keycode: String },
+    Sleep { ms: u64 },
+    // Runs adb subcommands concurrently, at most `max_concurrency` at a time
+    // (all at once when unset).
+    Parallel {
+        commands: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_concurrency: Option<usize>,
+    },
+    // Runs `steps` once per item with the item bound to `${<as>}`.
+    Loop {
+        over: LoopItems,
+        #[serde(rename = "as")]
+        var: String,
+        steps: Vec<WorkflowStep>,
+    },
+    // Every field of the step other than `type` and the common ones above
+    // ends up in `params`.
+    #[serde(skip)]
+    Custom { step_type: String, params: Map<String, Value> },
+}
+
+const BUILT_IN_STEP_TYPES: &[&str] = &[
+    "shell", "adb", "install", "uninstall", "push", "pull", "tap", "key_event", "sleep", "parallel", "loop",
+];
+
+impl Serialize for StepAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let StepAction::Custom { step_type, params } = self else {
+            return StepAction::serialize(self, serializer);
+        };
+        let mut map = serializer.serialize_map(Some(params.len() + 1))?;
+        map.serialize_entry("type", step_type)?;
+        for (key, value) in params {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for StepAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = Map::deserialize(deserializer)?;
+        let Some(Value::String(step_type)) = fields.remove("type") else {
+            return Err(D::Error::missing_field("type"));
+        };
+        if !BUILT_IN_STEP_TYPES.contains(&step_type.as_str()) {
+            return Ok(StepAction::Custom { step_type, params: fields });
+        }
+        fields.insert("type".to_string(), Value::String(step_type));
+        StepAction::deserialize(Value::Object(fields)).map_err(D::Error::custom)
+    }
+}
+
+// Either an explicit list, or a string (usually a placeholder such as
+// `${steps.list.output}`) split into items on newlines and commas.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LoopItems {
+    List(Vec<String>),
+    Text(String),
+}
+
+// Runs the step only if `step` ended with `status` and, when set, its output
+// contains `output_contains`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepCondition {
+    pub step: String,
+    #[serde(default)]
+    pub status: StepStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_contains: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    #[default]
+    Succeeded,
+    Failed,
+    Skipped,
+}
+
+impl StepStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StepStatus::Succeeded => "succeeded",
+            StepStatus::Failed => "failed",
+            StepStatus::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepResult {
+    // Steps inside a loop are named `<loop>[<index>].<step>`.
+    pub name: String,
+    pub status: StepStatus,
+    pub output: String,
+    pub error: Option<String>,
+    pub attempts: u32,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis", deserialize_with = "deserialize_millis")]
+    pub duration: Duration,
+    // Per-command results of a parallel step, in declaration order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<CommandResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WorkflowResult {
+    pub workflow: String,
+    pub device: String,
+    pub steps: Vec<StepResult>,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    pub cancelled: bool,
+    // Set when a checkpointed run could not save or clean up its checkpoint.
+    // The steps still ran; only resuming from the file is affected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoint_error: Option<String>,
+}
+
+impl WorkflowResult {
+    pub fn success(&self) -> bool {
+        !self.cancelled && self.steps.iter().all(|step| step.status != StepStatus::Failed)
+    }
+
+    pub fn step(&self, name: &str) -> Option<&StepResult> {
+        self.steps.iter().find(|step| step.name == name)
+    }
+
+    pub fn failed_steps(&self) -> impl Iterator<Item = &StepResult> {
+        self.steps.iter().filter(|step| step.status == StepStatus::Failed)
+    }
+
+    pub fn to_json(&self) -> Result<String, io::Error> {
+        serde_json::to_string_pretty(self).map_err(io::Error::other)
+    }
+
+    // One test suite named after the workflow, with a test case per step.
+    // Failed steps carry their error as the failure message and their output
+    // (plus any failed parallel commands) as the body; skipped steps carry
+    // the skip reason.
+    pub fn to_junit_xml(&self) -> String {
+        let count = |status| self.steps.iter().filter(|step| step.status == status).count();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(&self.workflow),
+            self.steps.len(),
+            count(StepStatus::Failed),
+            count(StepStatus::Skipped),
+            self.duration.as_secs_f64()
+        ));
+        xml.push_str(&format!(
+            "  <properties>\n    <property name=\"device\" value=\"{}\"/>\n  </properties>\n",
+            xml_escape(&self.device)
+        ));
+        for step in &self.steps {
+            let open = format!(
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                xml_escape(&step.name),
+                xml_escape(&self.workflow),
+                step.duration.as_secs_f64()
+            );
+            let reason = xml_escape(step.error.as_deref().unwrap_or_default());
+            match step.status {
+                StepStatus::Succeeded if step.output.is_empty() => xml.push_str(&format!("{}/>\n", open)),
+                StepStatus::Succeeded => xml.push_str(&format!(
+                    "{}>\n    <system-out>{}</system-out>\n  </testcase>\n",
+                    open,
+                    xml_escape(&step.output)
+                )),
+                StepStatus::Failed => {
+                    let mut details = step.output.clone();
+                    for command in step.commands.iter().filter(|command| !command.success()) {
+                        let error = command.error.as_deref().unwrap_or_default().trim_end();
+                        details.push_str(&format!("{}: {}\n", command.command, error));
+                    }
+                    xml.push_str(&format!(
+                        "{}>\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                        open,
+                        reason,
+                        xml_escape(&details)
+                    ))
+                }
+                StepStatus::Skipped => {
+                    xml.push_str(&format!("{}>\n    <skipped message=\"{}\"/>\n  </testcase>\n", open, reason))
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn serialize_millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    Ok(Duration::from_millis(u64::deserialize(deserializer)?))
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines are not valid XML.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// A workflow running in the background. `cancel` kills the step in flight,
+// which is recorded as failed, and the remaining steps are skipped.
+pub struct WorkflowHandle {
+    cancel: CancellationToken,
+    task: JoinHandle<Result<WorkflowResult, io::Error>>,
+}
+
+impl WorkflowHandle {
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    pub async fn join(self) -> Result<WorkflowResult, io::Error> {
+        self.task.await.map_err(io::Error::other)?
+    }
+}
+
+// What a custom step handler gets to work with. String values in `params`
+// have their placeholders resolved.
+pub struct StepContext<'a> {
+    pub adb: &'a ADB,
+    pub device: &'a str,
+    pub params: &'a Map<String, Value>,
+}
+
+// Runs a custom step type. The returned string is the step's output, so
+// conditions and `${steps.<step>.output}` work as for built-in steps; an
+// error fails the step, which is retried like any other.
+pub trait StepHandler: Send + Sync {
+    fn run(&self, context: &StepContext<'_>) -> Result<String, io::Error>;
+
+    // Used by the async runners. The default calls `run` in place, blocking
+    // the executor thread, so handlers that wait on anything should override
+    // it.
+    fn run_async<'a>(&'a self, context: &'a StepContext<'a>) -> BoxFuture<'a, Result<String, io::Error>> {
+        async move { self.run(context) }.boxed()
+    }
+}
+
+type StepHandlers = Arc<HashMap<String, Arc<dyn StepHandler>>>;
+
+// Live progress of runs started through a `WorkflowEngine`. Callbacks run on
+// the thread or task executing the workflow, between steps, so they should
+// return quickly; every method defaults to doing nothing.
+pub trait WorkflowObserver: Send + Sync {
+    // `step` is named like its result, e.g. `setup.<step>` or
+    // `<loop>[<index>].<step>`. Skipped steps are not started.
+    fn on_step_started(&self, _workflow: &str, _device: &str, _step: &str) {}
+
+    fn on_step_finished(&self, _workflow: &str, _device: &str, _result: &StepResult) {}
+
+    fn on_workflow_finished(&self, _result: &WorkflowResult) {}
+}
+
+type Observers = Arc<Vec<Arc<dyn WorkflowObserver>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandResult {
+    pub command: String,
+    pub output: String,
+    pub error: Option<String>,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis", deserialize_with = "deserialize_millis")]
+    pub duration: Duration,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    fn new(command: &str, outcome: Result<String, io::Error>, start: Instant) -> Self {
+        let (output, error) = match outcome {
+            Ok(output) => (output, None),
+            Err(error) => (String::new(), Some(error.to_string())),
+        };
+        CommandResult {
+            command: command.to_string(),
+            output,
+            error,
+            duration: start.elapsed(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchResult {
+    pub commands: Vec<CommandResult>,
+}
+
+impl BatchResult {
+    pub fn succeeded(&self) -> usize {
+        self.commands.iter().filter(|command| command.success()).count()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CommandResult> {
+        self.commands.iter().filter(|command| !command.success())
+    }
+}
+
+fn invalid_workflow(format: &str, error: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {} workflow: {}", format, error))
+}
+
+impl Workflow {
+    pub fn from_json(text: &str) -> Result<Self, io::Error> {
+        serde_json::from_str(text).map_err(|error| invalid_workflow("JSON", error))
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, io::Error> {
+        serde_yaml::from_str(text).map_err(|error| invalid_workflow("YAML", error))
+    }
+
+    // `.yaml` and `.yml` files are read as YAML, everything else as JSON.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => Self::from_yaml(&text),
+            _ => Self::from_json(&text),
+        }
+    }
+
+    pub async fn load_async(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        let text = tokio::fs::read_to_string(path).await?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => Self::from_yaml(&text),
+            _ => Self::from_json(&text),
+        }
+    }
+
+    pub fn with_var(mut self, name: &str, value: &str) -> Self {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // `setup` followed by `steps`, flagging the setup steps.
+    fn main_sequence(&self) -> impl Iterator<Item = (bool, &WorkflowStep)> {
+        let setup = self.setup.iter().map(|step| (true, step));
+        setup.chain(self.steps.iter().map(|step| (false, step)))
+    }
+}
+
+// What `resume_workflow` needs to pick a run up again: the workflow with its
+// variables, the device and the results of the top-level steps that were
+// done when it was saved.
+#[derive(Deserialize)]
+struct Checkpoint {
+    workflow: Workflow,
+    device: String,
+    completed: usize,
+    results: Vec<StepResult>,
+}
+
+impl Checkpoint {
+    fn parse(text: &str) -> Result<Self, io::Error> {
+        serde_json::from_str(text)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid workflow checkpoint: {}", error)))
+    }
+}
+
+// Written next to the checkpoint and renamed over it, so a crash mid-write
+// leaves the previous checkpoint intact.
+fn checkpoint_temp_path(path: &Path) -> PathBuf {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    PathBuf::from(temp)
+}
+
+fn save_checkpoint(path: &Path, text: &str) -> Result<(), io::Error> {
+    let temp = checkpoint_temp_path(path);
+    fs::write(&temp, text)?;
+    fs::rename(temp, path)
+}
+
+async fn save_checkpoint_async(path: &Path, text: &str) -> Result<(), io::Error> {
+    let temp = checkpoint_temp_path(path);
+    tokio::fs::write(&temp, text).await?;
+    tokio::fs::rename(temp, path).await
+}
+
+pub(crate) fn substitute(text: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String, io::Error> {
+    let mut resolved = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            resolved.push_str(&rest[..start - 1]);
+            resolved.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        resolved.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Unterminated placeholder in {:?}", text))
+        })?;
+        let name = after[..end].trim();
+        let value = lookup(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown variable ${{{}}}", name))
+        })?;
+        resolved.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+// A step after placeholder substitution, ready to hand to adb.
+enum PreparedAction {
+    Adb(String),
+    Sleep(Duration),
+    Loop { var: String, items: Vec<String> },
+    Parallel { commands: Vec<(String, String)>, max_concurrency: usize },
+    Custom {
+        handler: Arc<dyn StepHandler>,
+        device: String,
+        params: Map<String, Value>,
+    },
+}
+
+fn step_timed_out(timeout: Duration) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, format!("Step timed out after {:?}", timeout))
+}
+
+fn step_cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Workflow cancelled")
+}
+
+// A parallel step fails when any of its commands did; its output is the
+// commands' outputs in order.
+fn parallel_outcome(results: &[CommandResult]) -> Result<String, io::Error> {
+    let failed = results.iter().filter(|result| !result.success()).count();
+    if failed > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} parallel commands failed",
+            failed,
+            results.len()
+        )));
+    }
+    Ok(results.iter().map(|result| result.output.as_str()).collect())
+}
+
+// Everything about a run that does not depend on sync or async execution:
+// variable lookup, conditions and result bookkeeping.
+struct WorkflowRun<'w> {
+    workflow: &'w Workflow,
+    device: String,
+    results: Vec<StepResult>,
+    aborted: bool,
+    start: Instant,
+    // Loops currently running, innermost last, below the phase (`setup`,
+    // `on_failure`, `teardown`) if any.
+    scopes: Vec<LoopScope>,
+    cancel: CancellationToken,
+    handlers: StepHandlers,
+    // Inside `on_failure` or `teardown`.
+    in_hooks: bool,
+    observers: Observers,
+    checkpoint: Option<PathBuf>,
+    // Top-level steps of `setup` followed by `steps` that are done, either
+    // in this run or in the one it resumes.
+    completed: usize,
+    checkpoint_error: Option<io::Error>,
+}
+
+struct LoopScope {
+    // `<loop>[<index>]`, including the names of enclosing loops, or the name
+    // of a phase, which has no variable.
+    name: String,
+    var: String,
+    item: String,
+}
+
+impl<'w> WorkflowRun<'w> {
+    fn new(workflow: &'w Workflow, device: &str) -> Self {
+        WorkflowRun {
+            workflow,
+            device: device.to_string(),
+            results: Vec::new(),
+            aborted: false,
+            start: Instant::now(),
+            scopes: Vec::new(),
+            cancel: CancellationToken::new(),
+            handlers: StepHandlers::default(),
+            in_hooks: false,
+            observers: Observers::default(),
+            checkpoint: None,
+            completed: 0,
+            checkpoint_error: None,
+        }
+    }
+
+    fn with_checkpoint(mut self, path: &Path) -> Self {
+        self.checkpoint = Some(path.to_path_buf());
+        self
+    }
+
+    fn resume_from(mut self, checkpoint: &Checkpoint) -> Self {
+        self.completed = checkpoint.completed;
+        self.results = checkpoint.results.clone();
+        self
+    }
+
+    // The checkpoint to save once `completed` top-level steps are done.
+    // Nothing is saved past a failure that aborted the run or a cancel, so
+    // resuming retries the step that failed.
+    fn checkpoint(&mut self, completed: usize) -> Option<(PathBuf, String)> {
+        let path = self.checkpoint.clone()?;
+        if self.aborted || self.cancel.is_cancelled() {
+            return None;
+        }
+        self.completed = completed;
+        let checkpoint = serde_json::json!({
+            "workflow": self.workflow,
+            "device": self.device,
+            "completed": self.completed,
+            "results": self.results,
+        });
+        match serde_json::to_string_pretty(&checkpoint) {
+            Ok(text) => Some((path, text)),
+            Err(error) => {
+                self.checkpoint_error = Some(io::Error::other(error));
+                None
+            }
+        }
+    }
+
+    fn with_cancel(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    fn with_handlers(mut self, handlers: StepHandlers) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
+    fn with_observers(mut self, observers: Observers) -> Self {
+        self.observers = observers;
+        self
+    }
+
+    fn prefix(&self) -> String {
+        self.scopes
+            .iter()
+            .map(|scope| format!("{}.", scope.name))
+            .collect()
+    }
+
+    fn enter_loop(&mut self, step: &WorkflowStep, index: usize, var: &str, item: &str) {
+        let name = format!("{}{}[{}]", self.prefix(), step.name, index);
+        self.scopes.push(LoopScope {
+            name,
+            var: var.to_string(),
+            item: item.to_string(),
+        });
+    }
+
+    fn exit_loop(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn enter_phase(&mut self, name: &str, hooks: bool) {
+        self.scopes.push(LoopScope {
+            name: name.to_string(),
+            var: String::new(),
+            item: String::new(),
+        });
+        self.in_hooks = hooks;
+    }
+
+    fn exit_phase(&mut self) {
+        self.scopes.pop();
+        self.in_hooks = false;
+    }
+
+    fn failed(&self) -> bool {
+        self.cancel.is_cancelled() || self.results.iter().any(|result| result.status == StepStatus::Failed)
+    }
+
+    // Cancelling the workflow does not interrupt its hooks.
+    fn action_cancel(&self) -> CancellationToken {
+        if self.in_hooks {
+            CancellationToken::new()
+        } else {
+            self.cancel.clone()
+        }
+    }
+
+    // Finds a result by step name, preferring the innermost loop iteration so
+    // steps inside a loop can refer to their siblings by plain name.
+    fn find_result(&self, step: &str) -> Option<&StepResult> {
+        (0..=self.scopes.len()).rev().find_map(|depth| {
+            let prefix: String = self.scopes[..depth]
+                .iter()
+                .map(|scope| format!("{}.", scope.name))
+                .collect();
+            let name = format!("{}{}", prefix, step);
+            self.results.iter().rev().find(|result| result.name == name)
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Option<String> {
+        if name == "device" {
+            return Some(self.device.clone());
+        }
+        if let Some(variable) = name.strip_prefix("env.") {
+            return std::env::var(variable).ok();
+        }
+        if let Some((step, field)) = name.strip_prefix("steps.").and_then(|rest| rest.rsplit_once('.')) {
+            let result = self.find_result(step)?;
+            return match field {
+                "output" => Some(result.output.trim().to_string()),
+                "status" => Some(result.status.as_str().to_string()),
+                _ => None,
+            };
+        }
+        if let Some(scope) = self.scopes.iter().rev().find(|scope| !scope.var.is_empty() && scope.var == name) {
+            return Some(scope.item.clone());
+        }
+        self.workflow.vars.get(name).cloned()
+    }
+
+    fn resolve(&self, text: &str) -> Result<String, io::Error> {
+        substitute(text, |name| self.lookup(name))
+    }
+
+    fn resolve_value(&self, value: &Value) -> Result<Value, io::Error> {
+        Ok(match value {
+            Value::String(text) => Value::String(self.resolve(text)?),
+            Value::Array(items) => Value::Array(items.iter().map(|item| self.resolve_value(item)).collect::<Result<_, _>>()?),
+            Value::Object(fields) => Value::Object(self.resolve_map(fields)?),
+            other => other.clone(),
+        })
+    }
+
+    fn resolve_map(&self, fields: &Map<String, Value>) -> Result<Map<String, Value>, io::Error> {
+        fields
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), self.resolve_value(value)?)))
+            .collect()
+    }
+
+    fn prepare(&self, action: &StepAction) -> Result<PreparedAction, io::Error> {
+        let device = &self.device;
+        let command = match action {
+            StepAction::Shell { command } => format!("{} shell {}", device, self.resolve(command)?),
+            StepAction::Adb { args } => format!("{} {}", device, self.resolve(args)?),
+            StepAction::Install { apk } => format!("{} install {}", device, self.resolve(apk)?),
+            StepAction::Uninstall { package } => format!("{} uninstall {}", device, self.resolve(package)?),
+            StepAction::Push { local, remote } => {
+                format!("{} push {} {}", device, self.resolve(local)?, self.resolve(remote)?)
+            }
+            StepAction::Pull { remote, local } => {
+                format!("{} pull {} {}", device, self.resolve(remote)?, self.resolve(local)?)
+            }
+            StepAction::Tap { x, y } => format!("{} shell input tap {} {}", device, x, y),
+            StepAction::KeyEvent { keycode } => {
+                format!("{} shell input keyevent {}", device, self.resolve(keycode)?)
+            }
+            StepAction::Sleep { ms } => return Ok(PreparedAction::Sleep(Duration::from_millis(*ms))),
+            StepAction::Loop { over, var, .. } => {
+                let items = match over {
+                    LoopItems::List(items) => items.iter().map(|item| self.resolve(item)).collect::<Result<_, _>>()?,
+                    LoopItems::Text(text) => self
+                        .resolve(text)?
+                        .split(['\n', ','])
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                };
+                return Ok(PreparedAction::Loop { var: var.clone(), items });
+            }
+            StepAction::Parallel { commands, max_concurrency } => {
+                let commands = commands
+                    .iter()
+                    .map(|command| {
+                        let command = self.resolve(command)?;
+                        Ok((format!("{} {}", device, command), command))
+                    })
+                    .collect::<Result<Vec<_>, io::Error>>()?;
+                let max_concurrency = max_concurrency.unwrap_or(commands.len()).max(1);
+                return Ok(PreparedAction::Parallel { commands, max_concurrency });
+            }
+            StepAction::Custom { step_type, params } => {
+                let handler = self.handlers.get(step_type).cloned().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("No handler registered for step type {:?}", step_type),
+                    )
+                })?;
+                return Ok(PreparedAction::Custom {
+                    handler,
+                    device: device.clone(),
+                    params: self.resolve_map(params)?,
+                });
+            }
+        };
+        Ok(PreparedAction::Adb(command))
+    }
+
+    fn condition_met(&self, condition: &StepCondition) -> bool {
+        let Some(result) = self.find_result(&condition.step) else {
+            return false;
+        };
+        result.status == condition.status
+            && condition
+                .output_contains
+                .as_deref()
+                .is_none_or(|needle| result.output.contains(needle))
+    }
+
+    fn push_result(&mut self, result: StepResult) {
+        for observer in self.observers.iter() {
+            observer.on_step_finished(&self.workflow.name, &self.device, &result);
+        }
+        self.results.push(result);
+    }
+
+    fn skip(&mut self, step: &WorkflowStep, reason: &str) {
+        self.push_result(StepResult {
+            name: format!("{}{}", self.prefix(), step.name),
+            status: StepStatus::Skipped,
+            output: String::new(),
+            error: Some(reason.to_string()),
+            attempts: 0,
+            duration: Duration::ZERO,
+            commands: Vec::new(),
+        });
+    }
+
+    // Returns what to run for `step`, or None when the step was skipped or
+    // failed before running (its result is recorded either way).
+    fn plan(&mut self, step: &WorkflowStep) -> Option<PreparedAction> {
+        if self.cancel.is_cancelled() && !self.in_hooks {
+            self.skip(step, "Workflow cancelled");
+            return None;
+        }
+        if self.aborted && !self.in_hooks {
+            self.skip(step, "Workflow aborted by an earlier failure");
+            return None;
+        }
+        if let Some(condition) = &step.condition {
+            if !self.condition_met(condition) {
+                self.skip(step, "Condition not met");
+                return None;
+            }
+        }
+        match self.prepare(&step.action) {
+            Ok(action) => {
+                let name = format!("{}{}", self.prefix(), step.name);
+                for observer in self.observers.iter() {
+                    observer.on_step_started(&self.workflow.name, &self.device, &name);
+                }
+                Some(action)
+            }
+            Err(error) => {
+                self.record(step, Err(error), 0, Instant::now());
+                None
+            }
+        }
+    }
+
+    fn record(&mut self, step: &WorkflowStep, outcome: Result<String, io::Error>, attempts: u32, start: Instant) {
+        self.record_with_commands(step, outcome, Vec::new(), attempts, start);
+    }
+
+    fn record_with_commands(
+        &mut self,
+        step: &WorkflowStep,
+        outcome: Result<String, io::Error>,
+        commands: Vec<CommandResult>,
+        attempts: u32,
+        start: Instant,
+    ) {
+        let (status, output, error) = match outcome {
+            Ok(output) => (StepStatus::Succeeded, output, None),
+            Err(error) => (StepStatus::Failed, String::new(), Some(error.to_string())),
+        };
+        if status == StepStatus::Failed && !step.continue_on_error {
+            self.aborted = true;
+        }
+        self.push_result(StepResult {
+            name: format!("{}{}", self.prefix(), step.name),
+            status,
+            output,
+            error,
+            attempts,
+            duration: start.elapsed(),
+            commands,
+        });
+    }
+
+    // Number of results recorded so far; a loop compares it before and after
+    // its iterations to see whether anything inside failed.
+    fn mark(&self) -> usize {
+        self.results.len()
+    }
+
+    // A loop fails when any step inside it failed. With `continue_on_error`
+    // on the loop, that failure no longer aborts the rest of the workflow.
+    fn record_loop(&mut self, step: &WorkflowStep, mark: usize, iterations: usize, start: Instant) {
+        let failed = self.results[mark..]
+            .iter()
+            .filter(|result| result.status == StepStatus::Failed)
+            .count();
+        let outcome = if failed == 0 {
+            Ok(format!("{} iterations", iterations))
+        } else {
+            if step.continue_on_error {
+                self.aborted = false;
+            }
+            Err(io::Error::other(format!("{} step(s) failed inside the loop", failed)))
+        };
+        self.record(step, outcome, 1, start);
+    }
+
+    fn finish(self) -> WorkflowResult {
+        let result = WorkflowResult {
+            workflow: self.workflow.name.clone(),
+            device: self.device,
+            steps: self.results,
+            duration: self.start.elapsed(),
+            cancelled: self.cancel.is_cancelled(),
+            checkpoint_error: self.checkpoint_error.map(|error| error.to_string()),
+        };
+        for observer in self.observers.iter() {
+            observer.on_workflow_finished(&result);
+        }
+        result
+    }
+}
+
+impl ADB {
+    fn run_parallel(&self, commands: &[(String, String)], max_concurrency: usize, deadline: Option<Instant>) -> Vec<CommandResult> {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; commands.len()]);
+        std::thread::scope(|scope| {
+            for _ in 0..max_concurrency.min(commands.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some((full, command)) = commands.get(index) else {
+                        break;
+                    };
+                    let start = Instant::now();
+                    let outcome = match deadline {
+                        Some(deadline) => self.run_adb_until(full, deadline),
+                        None => self.run_adb(full),
+                    };
+                    let result = CommandResult::new(command, outcome, start);
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+        results.into_inner().unwrap().into_iter().flatten().collect()
+    }
+
+    async fn run_parallel_async(&self, commands: &[(String, String)], max_concurrency: usize) -> Vec<CommandResult> {
+        let mut results = vec![None; commands.len()];
+        let mut pending = commands.iter().cloned().enumerate();
+        let mut tasks = JoinSet::new();
+        loop {
+            while tasks.len() < max_concurrency {
+                let Some((index, (full, command))) = pending.next() else {
+                    break;
+                };
+                let adb = self.clone();
+                tasks.spawn(async move {
+                    let start = Instant::now();
+                    let outcome = adb.run_adb_unbounded_async(&full).await;
+                    (index, CommandResult::new(&command, outcome, start))
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            match joined {
+                Ok((index, result)) => results[index] = Some(result),
+                Err(error) => std::panic::resume_unwind(error.into_panic()),
+            }
+        }
+        results.into_iter().flatten().collect()
+    }
+
+    fn run_action(&self, action: &PreparedAction, timeout: Option<Duration>) -> (Result<String, io::Error>, Vec<CommandResult>) {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        match action {
+            PreparedAction::Adb(command) => {
+                let outcome = match deadline {
+                    Some(deadline) => self.run_adb_until(command, deadline),
+                    None => self.run_adb(command),
+                };
+                let outcome = outcome.map_err(|error| match timeout {
+                    Some(timeout) if error.kind() == io::ErrorKind::TimedOut => step_timed_out(timeout),
+                    _ => error,
+                });
+                (outcome, Vec::new())
+            }
+            PreparedAction::Sleep(duration) => match timeout {
+                Some(timeout) if timeout < *duration => {
+                    std::thread::sleep(timeout);
+                    (Err(step_timed_out(timeout)), Vec::new())
+                }
+                _ => {
+                    std::thread::sleep(*duration);
+                    (Ok(String::new()), Vec::new())
+                }
+            },
+            PreparedAction::Parallel { commands, max_concurrency } => {
+                let results = self.run_parallel(commands, *max_concurrency, deadline);
+                (parallel_outcome(&results), results)
+            }
+            // Handlers run in place, so the timeout cannot interrupt them
+            // here; the async runners enforce it.
+            PreparedAction::Custom { handler, device, params } => {
+                let context = StepContext { adb: self, device, params };
+                (handler.run(&context), Vec::new())
+            }
+            PreparedAction::Loop { .. } => (Ok(String::new()), Vec::new()),
+        }
+    }
+
+    async fn run_action_async(&self, action: &PreparedAction) -> (Result<String, io::Error>, Vec<CommandResult>) {
+        match action {
+            PreparedAction::Adb(command) => (self.run_adb_unbounded_async(command).await, Vec::new()),
+            PreparedAction::Sleep(duration) => {
+                tokio::time::sleep(*duration).await;
+                (Ok(String::new()), Vec::new())
+            }
+            PreparedAction::Parallel { commands, max_concurrency } => {
+                let results = self.run_parallel_async(commands, *max_concurrency).await;
+                (parallel_outcome(&results), results)
+            }
+            PreparedAction::Custom { handler, device, params } => {
+                let context = StepContext { adb: self, device, params };
+                (handler.run_async(&context).await, Vec::new())
+            }
+            PreparedAction::Loop { .. } => (Ok(String::new()), Vec::new()),
+        }
+    }
+
+    // Like the sync runner, steps are only bounded by their own `timeout_ms`
+    // and cancellation, not `ADB::timeout`. Dropping the action future on
+    // timeout or cancellation kills its adb processes, parallel tasks
+    // included.
+    async fn run_action_guarded(
+        &self,
+        action: &PreparedAction,
+        timeout: Option<Duration>,
+        cancel: &CancellationToken,
+    ) -> (Result<String, io::Error>, Vec<CommandResult>) {
+        let limited = async {
+            match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, self.run_action_async(action))
+                    .await
+                    .unwrap_or_else(|_| (Err(step_timed_out(timeout)), Vec::new())),
+                None => self.run_action_async(action).await,
+            }
+        };
+        tokio::select! {
+            outcome = limited => outcome,
+            _ = cancel.cancelled() => (Err(step_cancelled()), Vec::new()),
+        }
+    }
+
+    fn run_steps(&self, run: &mut WorkflowRun, steps: &[WorkflowStep]) {
+        for step in steps {
+            let Some(action) = run.plan(step) else {
+                continue;
+            };
+            let start = Instant::now();
+            if let (PreparedAction::Loop { var, items }, StepAction::Loop { steps: body, .. }) = (&action, &step.action) {
+                let mark = run.mark();
+                for (index, item) in items.iter().enumerate() {
+                    run.enter_loop(step, index, var, item);
+                    self.run_steps(run, body);
+                    run.exit_loop();
+                }
+                run.record_loop(step, mark, items.len(), start);
+                continue;
+            }
+
+            let timeout = step.timeout_ms.map(Duration::from_millis);
+            let mut attempts = 1;
+            let (mut outcome, mut commands) = self.run_action(&action, timeout);
+            while outcome.is_err() && attempts <= step.retries {
+                std::thread::sleep(Duration::from_millis(step.retry_delay_ms));
+                attempts += 1;
+                (outcome, commands) = self.run_action(&action, timeout);
+            }
+            run.record_with_commands(step, outcome, commands, attempts, start);
+        }
+    }
+
+    fn run_steps_async<'a>(&'a self, run: &'a mut WorkflowRun<'_>, steps: &'a [WorkflowStep]) -> BoxFuture<'a, ()> {
+        async move {
+            for step in steps {
+                let Some(action) = run.plan(step) else {
+                    continue;
+                };
+                let start = Instant::now();
+                if let (PreparedAction::Loop { var, items }, StepAction::Loop { steps: body, .. }) = (&action, &step.action) {
+                    let mark = run.mark();
+                    for (index, item) in items.iter().enumerate() {
+                        run.enter_loop(step, index, var, item);
+                        self.run_steps_async(run, body).await;
+                        run.exit_loop();
+                    }
+                    run.record_loop(step, mark, items.len(), start);
+                    continue;
+                }
+
+                let timeout = step.timeout_ms.map(Duration::from_millis);
+                let cancel = run.action_cancel();
+                let mut attempts = 1;
+                let (mut outcome, mut commands) = self.run_action_guarded(&action, timeout, &cancel).await;
+                while outcome.is_err() && attempts <= step.retries && !cancel.is_cancelled() {
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_millis(step.retry_delay_ms)) => {}
+                        _ = cancel.cancelled() => break,
+                    }
+                    attempts += 1;
+                    (outcome, commands) = self.run_action_guarded(&action, timeout, &cancel).await;
+                }
+                run.record_with_commands(step, outcome, commands, attempts, start);
+            }
+        }
+        .boxed()
+    }
+
+    // Top-level steps run one at a time so progress can be checkpointed
+    // between them.
+    fn run_workflow(&self, run: &mut WorkflowRun) {
+        let workflow = run.workflow;
+        for (position, (in_setup, step)) in workflow.main_sequence().enumerate().skip(run.completed) {
+            if in_setup {
+                run.enter_phase("setup", false);
+            }
+            self.run_steps(run, std::slice::from_ref(step));
+            if in_setup {
+                run.exit_phase();
+            }
+            if let Some((path, text)) = run.checkpoint(position + 1) {
+                if let Err(error) = save_checkpoint(&path, &text) {
+                    run.checkpoint_error.get_or_insert(error);
+                }
+            }
+        }
+        if run.failed() && !workflow.on_failure.is_empty() {
+            run.enter_phase("on_failure", true);
+            self.run_steps(run, &workflow.on_failure);
+            run.exit_phase();
+        }
+        if !workflow.teardown.is_empty() {
+            run.enter_phase("teardown", true);
+            self.run_steps(run, &workflow.teardown);
+            run.exit_phase();
+        }
+    }
+
+    async fn run_workflow_async(&self, run: &mut WorkflowRun<'_>) {
+        let workflow = run.workflow;
+        for (position, (in_setup, step)) in workflow.main_sequence().enumerate().skip(run.completed) {
+            if in_setup {
+                run.enter_phase("setup", false);
+            }
+            self.run_steps_async(run, std::slice::from_ref(step)).await;
+            if in_setup {
+                run.exit_phase();
+            }
+            if let Some((path, text)) = run.checkpoint(position + 1) {
+                if let Err(error) = save_checkpoint_async(&path, &text).await {
+                    run.checkpoint_error.get_or_insert(error);
+                }
+            }
+        }
+        if run.failed() && !workflow.on_failure.is_empty() {
+            run.enter_phase("on_failure", true);
+            self.run_steps_async(run, &workflow.on_failure).await;
+            run.exit_phase();
+        }
+        if !workflow.teardown.is_empty() {
+            run.enter_phase("teardown", true);
+            self.run_steps_async(run, &workflow.teardown).await;
+            run.exit_phase();
+        }
+    }
+
+    // Steps run in order; a failing step aborts the rest (recorded as
+    // skipped) unless it sets `continue_on_error`. Parallel steps run their
+    // commands on scoped threads.
+    pub fn execute_workflow(&self, device: &str, workflow: &Workflow) -> Result<WorkflowResult, io::Error> {
+        let mut run = WorkflowRun::new(workflow, device);
+        self.run_workflow(&mut run);
+        Ok(run.finish())
+    }
+
+    // Same semantics as `execute_workflow`; parallel steps run their
+    // commands as tokio tasks.
+    pub async fn execute_workflow_async(&self, device: &str, workflow: &Workflow) -> Result<WorkflowResult, io::Error> {
+        let mut run = WorkflowRun::new(workflow, device);
+        self.run_workflow_async(&mut run).await;
+        Ok(run.finish())
+    }
+
+    // Runs the workflow on a tokio task so it can be cancelled from outside.
+    // Must be called from within a tokio runtime.
+    pub fn spawn_workflow(&self, device: &str, workflow: Workflow) -> WorkflowHandle {
+        WorkflowEngine::new(self).spawn_workflow(device, workflow)
+    }
+
+    pub fn execute_workflow_with_checkpoint(
+        &self,
+        device: &str,
+        workflow: &Workflow,
+        checkpoint: impl AsRef<Path>,
+    ) -> Result<WorkflowResult, io::Error> {
+        WorkflowEngine::new(self).execute_workflow_with_checkpoint(device, workflow, checkpoint)
+    }
+
+    pub async fn execute_workflow_with_checkpoint_async(
+        &self,
+        device: &str,
+        workflow: &Workflow,
+        checkpoint: impl AsRef<Path>,
+    ) -> Result<WorkflowResult, io::Error> {
+        WorkflowEngine::new(self)
+            .execute_workflow_with_checkpoint_async(device, workflow, checkpoint)
+            .await
+    }
+
+    pub fn resume_workflow(&self, checkpoint: impl AsRef<Path>) -> Result<WorkflowResult, io::Error> {
+        WorkflowEngine::new(self).resume_workflow(checkpoint)
+    }
+
+    pub async fn resume_workflow_async(&self, checkpoint: impl AsRef<Path>) -> Result<WorkflowResult, io::Error> {
+        WorkflowEngine::new(self).resume_workflow_async(checkpoint).await
+    }
+
+    // Runs each adb subcommand (e.g. `shell pm clear com.example`) against
+    // `device`, carrying on past failures.
+    pub fn execute_batch_commands(&self, device: &str, commands: &[&str]) -> Result<BatchResult, io::Error> {
+        let commands = commands
+            .iter()
+            .map(|command| {
+                let start = Instant::now();
+                let outcome = self.run_adb(&format!("{} {}", device, command));
+                CommandResult::new(command, outcome, start)
+            })
+            .collect();
+        Ok(BatchResult { commands })
+    }
+
+    pub async fn execute_batch_commands_async(&self, device: &str, commands: &[&str]) -> Result<BatchResult, io::Error> {
+        let mut results = Vec::with_capacity(commands.len());
+        for command in commands {
+            let start = Instant::now();
+            let outcome = self.run_adb_async(&format!("{} {}", device, command)).await;
+            results.push(CommandResult::new(command, outcome, start));
+        }
+        Ok(BatchResult { commands: results })
+    }
+}
+
+// Runs workflows that use application-defined step types alongside the
+// built-in ones. `ADB::execute_workflow` behaves like an engine with nothing
+// registered, failing any custom step.
+#[derive(Clone)]
+pub struct WorkflowEngine {
+    adb: ADB,
+    handlers: StepHandlers,
+    observers: Observers,
+}
+
+impl WorkflowEngine {
+    pub fn new(adb: &ADB) -> Self {
+        WorkflowEngine {
+            adb: adb.clone(),
+            handlers: StepHandlers::default(),
+            observers: Observers::default(),
+        }
+    }
+
+    pub(crate) fn adb(&self) -> &ADB {
+        &self.adb
+    }
+
+    fn new_run<'w>(&self, workflow: &'w Workflow, device: &str) -> WorkflowRun<'w> {
+        WorkflowRun::new(workflow, device)
+            .with_handlers(self.handlers.clone())
+            .with_observers(self.observers.clone())
+    }
+
+    // Steps with `type: <name>` run `handler`. Built-in type names cannot be
+    // taken over.
+    pub fn register_step_type(&mut self, name: &str, handler: impl StepHandler + 'static) -> Result<(), io::Error> {
+        if BUILT_IN_STEP_TYPES.contains(&name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} is a built-in step type", name),
+            ));
+        }
+        Arc::make_mut(&mut self.handlers).insert(name.to_string(), Arc::new(handler));
+        Ok(())
+    }
+
+    pub fn add_observer(&mut self, observer: impl WorkflowObserver + 'static) {
+        Arc::make_mut(&mut self.observers).push(Arc::new(observer));
+    }
+
+    // Like `Workflow::validate`, but accepts the registered step types.
+    pub fn validate(&self, workflow: &Workflow) -> ValidationReport {
+        let step_types: Vec<&str> = self.handlers.keys().map(String::as_str).collect();
+        workflow.validate_with(&step_types)
+    }
+
+    pub fn execute_workflow(&self, device: &str, workflow: &Workflow) -> Result<WorkflowResult, io::Error> {
+        let mut run = self.new_run(workflow, device);
+        self.adb.run_workflow(&mut run);
+        Ok(run.finish())
+    }
+
+    pub async fn execute_workflow_async(&self, device: &str, workflow: &Workflow) -> Result<WorkflowResult, io::Error> {
+        let mut run = self.new_run(workflow, device);
+        self.adb.run_workflow_async(&mut run).await;
+        Ok(run.finish())
+    }
+
+    pub fn spawn_workflow(&self, device: &str, workflow: Workflow) -> WorkflowHandle {
+        let cancel = CancellationToken::new();
+        let engine = self.clone();
+        let device = device.to_string();
+        let token = cancel.clone();
+        let task = tokio::spawn(async move {
+            let mut run = engine.new_run(&workflow, &device).with_cancel(token);
+            engine.adb.run_workflow_async(&mut run).await;
+            Ok(run.finish())
+        });
+        WorkflowHandle { cancel, task }
+    }
+
+    fn run_checkpointed(&self, mut run: WorkflowRun, path: &Path) -> Result<WorkflowResult, io::Error> {
+        if let Some((path, text)) = run.checkpoint(run.completed) {
+            save_checkpoint(&path, &text)?;
+        }
+        self.adb.run_workflow(&mut run);
+        let mut result = run.finish();
+        if result.success() {
+            if let Err(error) = fs::remove_file(path) {
+                result.checkpoint_error.get_or_insert(error.to_string());
+            }
+        }
+        Ok(result)
+    }
+
+    async fn run_checkpointed_async(&self, mut run: WorkflowRun<'_>, path: &Path) -> Result<WorkflowResult, io::Error> {
+        if let Some((path, text)) = run.checkpoint(run.completed) {
+            save_checkpoint_async(&path, &text).await?;
+        }
+        self.adb.run_workflow_async(&mut run).await;
+        let mut result = run.finish();
+        if result.success() {
+            if let Err(error) = tokio::fs::remove_file(path).await {
+                result.checkpoint_error.get_or_insert(error.to_string());
+            }
+        }
+        Ok(result)
+    }
+
+    // Saves progress to `checkpoint` after every top-level step of `setup`
+    // and `steps`, so an interrupted or failed run can be continued with
+    // `resume_workflow`. The file is removed once the workflow succeeds.
+    // Saving failures after the first step do not stop the run; they are
+    // reported in `WorkflowResult::checkpoint_error`.
+    pub fn execute_workflow_with_checkpoint(
+        &self,
+        device: &str,
+        workflow: &Workflow,
+        checkpoint: impl AsRef<Path>,
+    ) -> Result<WorkflowResult, io::Error> {
+        let path = checkpoint.as_ref();
+        let run = self.new_run(workflow, device).with_checkpoint(path);
+        self.run_checkpointed(run, path)
+    }
+
+    pub async fn execute_workflow_with_checkpoint_async(
+        &self,
+        device: &str,
+        workflow: &Workflow,
+        checkpoint: impl AsRef<Path>,
+    ) -> Result<WorkflowResult, io::Error> {
+        let path = checkpoint.as_ref();
+        let run = self.new_run(workflow, device).with_checkpoint(path);
+        self.run_checkpointed_async(run, path).await
+    }
+
+    // Continues a checkpointed run on the same device from the first
+    // top-level step that had not completed; earlier results are kept in
+    // the returned result. Hooks run again as for a fresh run.
+    pub fn resume_workflow(&self, checkpoint: impl AsRef<Path>) -> Result<WorkflowResult, io::Error> {
+        let path = checkpoint.as_ref();
+        let saved = Checkpoint::parse(&fs::read_to_string(path)?)?;
+        let run = self
+            .new_run(&saved.workflow, &saved.device)
+            .with_checkpoint(path)
+            .resume_from(&saved);
+        self.run_checkpointed(run, path)
+    }
+
+    pub async fn resume_workflow_async(&self, checkpoint: impl AsRef<Path>) -> Result<WorkflowResult, io::Error> {
+        let path = checkpoint.as_ref();
+        let saved = Checkpoint::parse(&tokio::fs::read_to_string(path).await?)?;
+        let run = self
+            .new_run(&saved.workflow, &saved.device)
+            .with_checkpoint(path)
+            .resume_from(&saved);
+        self.run_checkpointed_async(run, path).await
+    }
+}
@@ -0,0 +1,213 @@
+// src/battery.rs
+
+use crate::ADB;
+use futures::stream::{self, Stream};
+use std::io;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatteryStatus {
+    #[default]
+    Unknown,
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+}
+
+impl BatteryStatus {
+    // Values from android.os.BatteryManager.BATTERY_STATUS_*.
+    fn from_code(code: u32) -> Self {
+        match code {
+            2 => BatteryStatus::Charging,
+            3 => BatteryStatus::Discharging,
+            4 => BatteryStatus::NotCharging,
+            5 => BatteryStatus::Full,
+            _ => BatteryStatus::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatteryHealth {
+    #[default]
+    Unknown,
+    Good,
+    Overheat,
+    Dead,
+    OverVoltage,
+    Failure,
+    Cold,
+}
+
+impl BatteryHealth {
+    // Values from android.os.BatteryManager.BATTERY_HEALTH_*.
+    fn from_code(code: u32) -> Self {
+        match code {
+            2 => BatteryHealth::Good,
+            3 => BatteryHealth::Overheat,
+            4 => BatteryHealth::Dead,
+            5 => BatteryHealth::OverVoltage,
+            6 => BatteryHealth::Failure,
+            7 => BatteryHealth::Cold,
+            _ => BatteryHealth::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatteryInfo {
+    pub level_pct: u8,
+    pub status: BatteryStatus,
+    pub health: BatteryHealth,
+    pub present: bool,
+    pub ac_powered: bool,
+    pub usb_powered: bool,
+    pub wireless_powered: bool,
+    pub voltage_mv: u32,
+    pub temperature_c: f32,
+    pub technology: String,
+}
+
+impl BatteryInfo {
+    pub fn is_charging(&self) -> bool {
+        self.status == BatteryStatus::Charging || self.status == BatteryStatus::Full
+    }
+
+    pub fn is_plugged(&self) -> bool {
+        self.ac_powered || self.usb_powered || self.wireless_powered
+    }
+
+    fn parse(output: &str) -> Self {
+        let mut info = BatteryInfo::default();
+        let mut level = 0u32;
+        let mut scale = 100u32;
+        for line in output.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "AC powered" => info.ac_powered = value == "true",
+                "USB powered" => info.usb_powered = value == "true",
+                "Wireless powered" => info.wireless_powered = value == "true",
+                "present" => info.present = value == "true",
+                "status" => info.status = BatteryStatus::from_code(value.parse().unwrap_or(0)),
+                "health" => info.health = BatteryHealth::from_code(value.parse().unwrap_or(0)),
+                "level" => level = value.parse().unwrap_or(0),
+                "scale" => scale = value.parse().unwrap_or(100),
+                "voltage" => info.voltage_mv = value.parse().unwrap_or(0),
+                // Reported in tenths of a degree Celsius.
+                "temperature" => info.temperature_c = value.parse::<f32>().unwrap_or(0.0) / 10.0,
+                "technology" => info.technology = value.to_string(),
+                _ => {}
+            }
+        }
+        info.level_pct = (level * 100 / scale.max(1)).min(100) as u8;
+        info
+    }
+}
+
+fn timed_out(what: &str, timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("Battery did not reach {} within {:?}", what, timeout),
+    )
+}
+
+impl ADB {
+    pub fn get_battery_info(&self, device: &str) -> Result<BatteryInfo, io::Error> {
+        let output = self.run_adb(&format!("{} shell dumpsys battery", device))?;
+        Ok(BatteryInfo::parse(&output))
+    }
+
+    pub async fn get_battery_info_async(&self, device: &str) -> Result<BatteryInfo, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell dumpsys battery", device)).await?;
+        Ok(BatteryInfo::parse(&output))
+    }
+
+    // Yields a sample every `interval` until the stream is dropped. Errors
+    // are yielded rather than ending the stream so a transient adb failure
+    // doesn't stop monitoring.
+    pub fn monitor_battery<'a>(
+        &'a self,
+        device: &'a str,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<BatteryInfo, io::Error>> + 'a {
+        let interval = interval.max(Duration::from_millis(1));
+        stream::unfold(None, move |ticker| async move {
+            let mut ticker = ticker.unwrap_or_else(|| tokio::time::interval(interval));
+            ticker.tick().await;
+            Some((self.get_battery_info_async(device).await, Some(ticker)))
+        })
+    }
+
+    fn wait_for_battery(
+        &self,
+        device: &str,
+        timeout: Duration,
+        what: &str,
+        ready: impl Fn(&BatteryInfo) -> bool,
+    ) -> Result<BatteryInfo, io::Error> {
+        let start = Instant::now();
+        loop {
+            let info = self.get_battery_info(device)?;
+            if ready(&info) {
+                return Ok(info);
+            }
+            if start.elapsed() >= timeout {
+                return Err(timed_out(what, timeout));
+            }
+            std::thread::sleep(POLL_INTERVAL.min(timeout));
+        }
+    }
+
+    async fn wait_for_battery_async(
+        &self,
+        device: &str,
+        timeout: Duration,
+        what: &str,
+        ready: impl Fn(&BatteryInfo) -> bool,
+    ) -> Result<BatteryInfo, io::Error> {
+        let start = Instant::now();
+        loop {
+            let info = self.get_battery_info_async(device).await?;
+            if ready(&info) {
+                return Ok(info);
+            }
+            if start.elapsed() >= timeout {
+                return Err(timed_out(what, timeout));
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(timeout)).await;
+        }
+    }
+
+    pub fn wait_for_battery_level(&self, device: &str, min_pct: u8, timeout: Duration) -> Result<BatteryInfo, io::Error> {
+        self.wait_for_battery(device, timeout, &format!("{}%", min_pct), |info| info.level_pct >= min_pct)
+    }
+
+    pub async fn wait_for_battery_level_async(&self, device: &str, min_pct: u8, timeout: Duration) -> Result<BatteryInfo, io::Error> {
+        self.wait_for_battery_async(device, timeout, &format!("{}%", min_pct), |info| info.level_pct >= min_pct)
+            .await
+    }
+
+    pub fn wait_for_battery_temperature(&self, device: &str, max_celsius: f32, timeout: Duration) -> Result<BatteryInfo, io::Error> {
+        self.wait_for_battery(device, timeout, &format!("{}°C", max_celsius), |info| {
+            info.temperature_c <= max_celsius
+        })
+    }
+
+    pub async fn wait_for_battery_temperature_async(
+        &self,
+        device: &str,
+        max_celsius: f32,
+        timeout: Duration,
+    ) -> Result<BatteryInfo, io::Error> {
+        self.wait_for_battery_async(device, timeout, &format!("{}°C", max_celsius), |info| {
+            info.temperature_c <= max_celsius
+        })
+        .await
+    }
+}
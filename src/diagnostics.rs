@@ -0,0 +1,445 @@
+// src/diagnostics.rs
+
+use crate::{scratch_tag, shell_quote, ADB};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Output;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PingStats {
+    pub sent: u32,
+    pub received: u32,
+    pub loss_pct: f32,
+    pub min_rtt: Option<Duration>,
+    pub avg_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+}
+
+impl PingStats {
+    // Parses the summary that both iputils and toybox ping print:
+    // `4 packets transmitted, 4 received, 0% packet loss, time 3004ms`
+    // `rtt min/avg/max/mdev = 9.812/11.204/13.975/1.602 ms`
+    fn parse(host: &str, output: &Output) -> Result<Self, io::Error> {
+        let text = String::from_utf8_lossy(&output.stdout);
+        let mut stats = None;
+        for line in text.lines().map(str::trim) {
+            if line.contains("packets transmitted") {
+                let mut parsed = PingStats::default();
+                for field in line.split(',').map(str::trim) {
+                    let value = field.split_whitespace().next().unwrap_or_default();
+                    if field.ends_with("packets transmitted") {
+                        parsed.sent = value.parse().unwrap_or(0);
+                    } else if field.ends_with("received") {
+                        parsed.received = value.parse().unwrap_or(0);
+                    } else if field.ends_with("packet loss") {
+                        parsed.loss_pct = value.trim_end_matches('%').parse().unwrap_or(100.0);
+                    }
+                }
+                stats = Some(parsed);
+            } else if let (Some(stats), Some((_, values))) = (stats.as_mut(), line.split_once("min/avg/max")) {
+                let rtts: Vec<Option<Duration>> = values
+                    .split_once('=')
+                    .map(|(_, values)| values.trim().trim_end_matches("ms").trim())
+                    .unwrap_or_default()
+                    .split('/')
+                    .map(|value| value.parse::<f64>().ok().map(|ms| Duration::from_secs_f64(ms / 1000.0)))
+                    .collect();
+                stats.min_rtt = rtts.first().copied().flatten();
+                stats.avg_rtt = rtts.get(1).copied().flatten();
+                stats.max_rtt = rtts.get(2).copied().flatten();
+            }
+        }
+
+        stats.ok_or_else(|| {
+            io::Error::other(format!(
+                "Failed to ping {}: {}",
+                host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpProbe {
+    // None when the client could not reach the server at all.
+    pub status: Option<u16>,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HttpClient {
+    Curl,
+    Wget,
+}
+
+impl HttpClient {
+    const ALL: [HttpClient; 2] = [HttpClient::Curl, HttpClient::Wget];
+
+    fn name(self) -> &'static str {
+        match self {
+            HttpClient::Curl => "curl",
+            HttpClient::Wget => "wget",
+        }
+    }
+
+    fn command(self, device: &str, url: &str) -> String {
+        match self {
+            HttpClient::Curl => format!(
+                "{} shell curl -s -o /dev/null -w '%{{http_code}}' {}",
+                device,
+                shell_quote(url)
+            ),
+            HttpClient::Wget => format!("{} shell wget -q -S -O /dev/null {} 2>&1", device, shell_quote(url)),
+        }
+    }
+
+    // curl prints just the code (`000` on connection failure); wget -S
+    // echoes the response headers, with the last status line winning after
+    // redirects.
+    fn parse_status(self, output: &Output) -> Option<u16> {
+        let text = String::from_utf8_lossy(&output.stdout);
+        match self {
+            HttpClient::Curl => text.trim().parse().ok().filter(|code| *code != 0),
+            HttpClient::Wget => text
+                .lines()
+                .rev()
+                .map(str::trim)
+                .filter(|line| line.starts_with("HTTP/"))
+                .find_map(|line| line.split_whitespace().nth(1)?.parse().ok()),
+        }
+    }
+}
+
+fn no_http_client(device: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Neither curl nor wget is available on {}", device),
+    )
+}
+
+impl ADB {
+    // ping exits non-zero when no replies arrive, so the summary is parsed
+    // regardless of the exit status.
+    pub fn ping_host(&self, device: &str, host: &str, count: u32) -> Result<PingStats, io::Error> {
+        let output = self.exec_shell(&format!("{} shell ping -c {} {}", device, count, shell_quote(host)))?;
+        PingStats::parse(host, &output)
+    }
+
+    pub async fn ping_host_async(&self, device: &str, host: &str, count: u32) -> Result<PingStats, io::Error> {
+        let output = self
+            .exec_shell_async(&format!("{} shell ping -c {} {}", device, count, shell_quote(host)))
+            .await?;
+        PingStats::parse(host, &output)
+    }
+
+    fn find_http_client(&self, device: &str) -> Option<HttpClient> {
+        HttpClient::ALL
+            .into_iter()
+            .find(|client| self.run_adb(&format!("{} shell which {}", device, client.name())).is_ok())
+    }
+
+    async fn find_http_client_async(&self, device: &str) -> Option<HttpClient> {
+        for client in HttpClient::ALL {
+            if self
+                .run_adb_async(&format!("{} shell which {}", device, client.name()))
+                .await
+                .is_ok()
+            {
+                return Some(client);
+            }
+        }
+        None
+    }
+
+    // The request is made from the device itself, so it goes through the
+    // device's own DNS, proxy and VPN settings. `elapsed` includes the adb
+    // round-trip.
+    pub fn http_probe(&self, device: &str, url: &str) -> Result<HttpProbe, io::Error> {
+        let client = self.find_http_client(device).ok_or_else(|| no_http_client(device))?;
+        let start = Instant::now();
+        let output = self.exec_shell(&client.command(device, url))?;
+        Ok(HttpProbe {
+            status: client.parse_status(&output),
+            elapsed: start.elapsed(),
+        })
+    }
+
+    pub async fn http_probe_async(&self, device: &str, url: &str) -> Result<HttpProbe, io::Error> {
+        let client = self
+            .find_http_client_async(device)
+            .await
+            .ok_or_else(|| no_http_client(device))?;
+        let start = Instant::now();
+        let output = self.exec_shell_async(&client.command(device, url)).await?;
+        Ok(HttpProbe {
+            status: client.parse_status(&output),
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+const LINK_PROBE_ROUNDS: u32 = 5;
+const LINK_PROBE_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkQuality {
+    pub min_latency: Duration,
+    pub avg_latency: Duration,
+    pub max_latency: Duration,
+    pub push_bytes_per_sec: f64,
+    pub pull_bytes_per_sec: f64,
+}
+
+impl LinkQuality {
+    fn from_samples(latencies: &[Duration], push: Duration, pull: Duration) -> Self {
+        let rate = |elapsed: Duration| LINK_PROBE_BYTES as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        LinkQuality {
+            min_latency: latencies.iter().copied().min().unwrap_or_default(),
+            avg_latency: latencies.iter().sum::<Duration>() / latencies.len().max(1) as u32,
+            max_latency: latencies.iter().copied().max().unwrap_or_default(),
+            push_bytes_per_sec: rate(push),
+            pull_bytes_per_sec: rate(pull),
+        }
+    }
+}
+
+// Paths for the throughput probe: a fixed-size local file, where the pulled
+// copy lands, and the remote scratch file.
+struct LinkProbeFiles {
+    upload: PathBuf,
+    download: PathBuf,
+    remote: String,
+}
+
+impl LinkProbeFiles {
+    fn create(device: &str) -> Result<Self, io::Error> {
+        let tag = scratch_tag(device);
+        let dir = std::env::temp_dir();
+        let files = LinkProbeFiles {
+            upload: dir.join(format!("rust-adb-link-{}.bin", tag)),
+            download: dir.join(format!("rust-adb-link-{}.pulled.bin", tag)),
+            remote: format!("/data/local/tmp/rust-adb-link-{}.bin", tag),
+        };
+        let payload: Vec<u8> = (0..LINK_PROBE_BYTES).map(|i| (i % 251) as u8).collect();
+        fs::write(&files.upload, payload)?;
+        Ok(files)
+    }
+}
+
+impl Drop for LinkProbeFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.upload);
+        let _ = fs::remove_file(&self.download);
+    }
+}
+
+impl ADB {
+    // Latency is the full `adb shell` round-trip, so it includes process
+    // start-up on both ends; compare devices measured from the same host.
+    pub fn measure_link_quality(&self, device: &str) -> Result<LinkQuality, io::Error> {
+        let mut latencies = Vec::new();
+        for _ in 0..LINK_PROBE_ROUNDS {
+            let start = Instant::now();
+            self.run_adb(&format!("{} shell echo", device))?;
+            latencies.push(start.elapsed());
+        }
+
+        let files = LinkProbeFiles::create(device)?;
+        let start = Instant::now();
+        self.push(device, &files.upload.to_string_lossy(), &files.remote)?;
+        let push = start.elapsed();
+        let start = Instant::now();
+        let pulled = self.pull(device, &files.remote, &files.download.to_string_lossy());
+        let pull = start.elapsed();
+        let _ = self.run_adb(&format!("{} shell rm -f {}", device, files.remote));
+        pulled?;
+
+        Ok(LinkQuality::from_samples(&latencies, push, pull))
+    }
+
+    pub async fn measure_link_quality_async(&self, device: &str) -> Result<LinkQuality, io::Error> {
+        let mut latencies = Vec::new();
+        for _ in 0..LINK_PROBE_ROUNDS {
+            let start = Instant::now();
+            self.run_adb_async(&format!("{} shell echo", device)).await?;
+            latencies.push(start.elapsed());
+        }
+
+        let files = LinkProbeFiles::create(device)?;
+        let start = Instant::now();
+        self.push_async(device, &files.upload.to_string_lossy(), &files.remote)
+            .await?;
+        let push = start.elapsed();
+        let start = Instant::now();
+        let pulled = self
+            .pull_async(device, &files.remote, &files.download.to_string_lossy())
+            .await;
+        let pull = start.elapsed();
+        let _ = self
+            .run_adb_async(&format!("{} shell rm -f {}", device, files.remote))
+            .await;
+        pulled?;
+
+        Ok(LinkQuality::from_samples(&latencies, push, pull))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transport {
+    Cellular,
+    Wifi,
+    Bluetooth,
+    Ethernet,
+    Vpn,
+    WifiAware,
+    Usb,
+    Other(String),
+}
+
+impl Transport {
+    fn parse(name: &str) -> Self {
+        match name {
+            "CELLULAR" => Transport::Cellular,
+            "WIFI" => Transport::Wifi,
+            "BLUETOOTH" => Transport::Bluetooth,
+            "ETHERNET" => Transport::Ethernet,
+            "VPN" => Transport::Vpn,
+            "WIFI_AWARE" => Transport::WifiAware,
+            "USB" => Transport::Usb,
+            other => Transport::Other(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkStatus {
+    pub id: u32,
+    pub transports: Vec<Transport>,
+    pub interface: Option<String>,
+    pub is_default: bool,
+    pub validated: bool,
+    pub captive_portal: bool,
+    pub metered: bool,
+}
+
+impl NetworkStatus {
+    pub fn is_vpn(&self) -> bool {
+        self.transports.contains(&Transport::Vpn)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectivityReport {
+    pub default_network: Option<u32>,
+    pub networks: Vec<NetworkStatus>,
+}
+
+// Returns what sits between `key{` and its matching brace.
+fn braced<'t>(text: &'t str, key: &str) -> Option<&'t str> {
+    let start = text.find(&format!("{}{{", key))? + key.len() + 1;
+    let mut depth = 1;
+    for (offset, c) in text[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[start..start + offset]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+impl ConnectivityReport {
+    pub fn active_network(&self) -> Option<&NetworkStatus> {
+        self.networks.iter().find(|network| network.is_default)
+    }
+
+    pub fn vpn_active(&self) -> bool {
+        self.networks.iter().any(NetworkStatus::is_vpn)
+    }
+
+    pub fn captive_portal(&self) -> bool {
+        self.networks.iter().any(|network| network.captive_portal)
+    }
+
+    pub fn has_validated_internet(&self) -> bool {
+        self.active_network().is_some_and(|network| network.validated)
+    }
+
+    // Each connected network is dumped on a single line:
+    // `NetworkAgentInfo{network{100}  ni{WIFI CONNECTED ...}  lp{{InterfaceName: wlan0 ...}}
+    //  nc{[ Transports: WIFI Capabilities: INTERNET&NOT_METERED&VALIDATED ...]}
+    //  ... lastValidated{true} ... lastCaptivePortalDetected{false} ...}`
+    fn parse(dump: &str) -> Self {
+        let default_network = dump.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix("Active default network:")?
+                .trim()
+                .parse()
+                .ok()
+        });
+
+        let networks = dump
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("NetworkAgentInfo{"))
+            .filter_map(|line| {
+                let id = braced(line, "network")?.trim().parse().ok()?;
+                let caps = braced(line, "nc").unwrap_or_default();
+                let transports = caps
+                    .split("Transports: ")
+                    .nth(1)
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .map(|names| names.split('|').map(Transport::parse).collect())
+                    .unwrap_or_default();
+                let capabilities: Vec<&str> = caps
+                    .split("Capabilities: ")
+                    .nth(1)
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .map(|names| names.split('&').collect())
+                    .unwrap_or_default();
+                let interface = braced(line, "lp")
+                    .and_then(|lp| lp.split("InterfaceName: ").nth(1))
+                    .and_then(|rest| rest.split_whitespace().next())
+                    .map(str::to_string);
+                let flag = |key: &str| braced(line, key) == Some("true");
+
+                Some(NetworkStatus {
+                    id,
+                    transports,
+                    interface,
+                    is_default: default_network == Some(id),
+                    validated: capabilities.contains(&"VALIDATED") || flag("lastValidated"),
+                    captive_portal: capabilities.contains(&"CAPTIVE_PORTAL") || flag("lastCaptivePortalDetected"),
+                    metered: !capabilities.contains(&"NOT_METERED"),
+                })
+            })
+            .collect();
+
+        ConnectivityReport {
+            default_network,
+            networks,
+        }
+    }
+}
+
+impl ADB {
+    pub fn get_connectivity_report(&self, device: &str) -> Result<ConnectivityReport, io::Error> {
+        let dump = self.run_adb(&format!("{} shell dumpsys connectivity", device))?;
+        Ok(ConnectivityReport::parse(&dump))
+    }
+
+    pub async fn get_connectivity_report_async(&self, device: &str) -> Result<ConnectivityReport, io::Error> {
+        let dump = self
+            .run_adb_async(&format!("{} shell dumpsys connectivity", device))
+            .await?;
+        Ok(ConnectivityReport::parse(&dump))
+    }
+}
@@ -0,0 +1,443 @@
+// src/display.rs
+
+use crate::ADB;
+use std::io;
+
+// `wm size` and `wm density` both print a `Physical ...:` line and, when an
+// override is active, an `Override ...:` line.
+fn parse_wm_output<T>(output: &str, parse: impl Fn(&str) -> Option<T>) -> (Option<T>, Option<T>) {
+    let mut physical = None;
+    let mut override_value = None;
+    for line in output.lines() {
+        let Some((label, value)) = line.split_once(':') else {
+            continue;
+        };
+        let Some(value) = parse(value.trim()) else {
+            continue;
+        };
+        if label.contains("Override") {
+            override_value = Some(value);
+        } else {
+            physical = Some(value);
+        }
+    }
+    (physical, override_value)
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (w, h) = value.split_once('x')?;
+    Some((w.parse().ok()?, h.parse().ok()?))
+}
+
+// Prefers the override size when one is set since that is what input
+// coordinates are mapped against.
+fn parse_wm_size(output: &str) -> Option<(u32, u32)> {
+    let (physical, override_size) = parse_wm_output(output, parse_size);
+    override_size.or(physical)
+}
+
+// `dumpsys window displays` has `mCurrentRotation=ROTATION_90` on newer
+// builds and `mRotation=1` (or `mCurrentRotation=1`) on older ones.
+fn parse_display_rotation(output: &str) -> Option<u32> {
+    output
+        .split_whitespace()
+        .find_map(|field| {
+            let value = field
+                .strip_prefix("mCurrentRotation=")
+                .or_else(|| field.strip_prefix("mRotation="))?;
+            match value.strip_prefix("ROTATION_") {
+                Some(degrees) => degrees.parse::<u32>().ok().map(|degrees| degrees / 90),
+                None => value.parse().ok(),
+            }
+        })
+        .filter(|&rotation| rotation < 4)
+}
+
+// `wm size` reports the natural size, while input coordinates follow the
+// current rotation.
+fn rotate_size((width, height): (u32, u32), rotation: u32) -> (u32, u32) {
+    if rotation % 2 == 1 {
+        (height, width)
+    } else {
+        (width, height)
+    }
+}
+
+// Newer builds report `mWakefulness=Awake`, older ones only the display
+// power state, so both are checked before falling back to `dumpsys display`.
+fn parse_screen_on(power: &str) -> Option<bool> {
+    power.lines().map(str::trim).find_map(|line| {
+        if let Some(state) = line.strip_prefix("mWakefulness=") {
+            Some(state == "Awake")
+        } else if let Some(state) = line.strip_prefix("Display Power: state=") {
+            Some(state == "ON")
+        } else {
+            line.strip_prefix("mScreenOn=").map(|state| state == "true")
+        }
+    })
+}
+
+fn parse_display_state(display: &str) -> Option<bool> {
+    display.lines().map(str::trim).find_map(|line| {
+        line.strip_prefix("mScreenState=").map(|state| state == "ON")
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrType {
+    DolbyVision,
+    Hdr10,
+    Hlg,
+    Hdr10Plus,
+    Other(u32),
+}
+
+impl HdrType {
+    // Values from android.view.Display.HdrCapabilities.HDR_TYPE_*.
+    fn from_code(code: u32) -> Self {
+        match code {
+            1 => HdrType::DolbyVision,
+            2 => HdrType::Hdr10,
+            3 => HdrType::Hlg,
+            4 => HdrType::Hdr10Plus,
+            other => HdrType::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Insets {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayInfo {
+    pub width: u32,
+    pub height: u32,
+    pub density_dpi: Option<u32>,
+    pub physical_xdpi: Option<f32>,
+    pub physical_ydpi: Option<f32>,
+    pub refresh_rates: Vec<f32>,
+    pub hdr_types: Vec<HdrType>,
+    pub cutout_insets: Option<Insets>,
+}
+
+fn leading_number<T: std::str::FromStr>(text: &str) -> Option<T> {
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    text[..end].parse().ok()
+}
+
+impl DisplayInfo {
+    // Parses the first `DisplayDeviceInfo{...}` line of `dumpsys display`,
+    // which describes the built-in panel, e.g.
+    // `DisplayDeviceInfo{"Built-in Screen": ..., 1080 x 2400, modeId 1, ...,
+    //  supportedModes [{id=1, width=1080, height=2400, fps=60.0}, ...], ...,
+    //  density 420, 409.432 x 411.891 dpi, ..., cutout DisplayCutout{insets=Rect(0, 91 - 0, 0) ...}`.
+    fn parse(output: &str) -> Result<Self, io::Error> {
+        let line = output
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("DisplayDeviceInfo{"))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No DisplayDeviceInfo in dumpsys display"))?;
+        let fields: Vec<&str> = line.split(", ").map(str::trim).collect();
+
+        let mut info = DisplayInfo::default();
+        if let Some((w, h)) = fields.iter().find_map(|field| {
+            let (w, h) = field.split_once(" x ")?;
+            Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?))
+        }) {
+            info.width = w;
+            info.height = h;
+        }
+
+        info.density_dpi = fields
+            .iter()
+            .find_map(|field| field.strip_prefix("density ").and_then(|value| value.parse().ok()));
+
+        if let Some((x, y)) = fields.iter().find_map(|field| {
+            let (x, y) = field.strip_suffix(" dpi")?.split_once(" x ")?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        }) {
+            info.physical_xdpi = Some(x);
+            info.physical_ydpi = Some(y);
+        }
+
+        for part in line.split("fps=").skip(1) {
+            if let Some(fps) = leading_number::<f32>(part) {
+                if !info.refresh_rates.iter().any(|known| (known - fps).abs() < 0.01) {
+                    info.refresh_rates.push(fps);
+                }
+            }
+        }
+        info.refresh_rates.sort_by(|a, b| a.total_cmp(b));
+
+        if let Some(types) = line
+            .split_once("mSupportedHdrTypes=[")
+            .and_then(|(_, rest)| rest.split_once(']'))
+        {
+            info.hdr_types = types
+                .0
+                .split(',')
+                .filter_map(|code| code.trim().parse().ok())
+                .map(HdrType::from_code)
+                .collect();
+        }
+
+        if let Some((rect, _)) = line
+            .split_once("insets=Rect(")
+            .and_then(|(_, rest)| rest.split_once(')'))
+        {
+            let values: Vec<u32> = rect
+                .split([',', '-'])
+                .filter_map(|value| value.trim().parse().ok())
+                .collect();
+            if let [left, top, right, bottom] = values[..] {
+                info.cutout_insets = Some(Insets { left, top, right, bottom });
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn max_refresh_rate(&self) -> Option<f32> {
+        self.refresh_rates.last().copied()
+    }
+
+    pub fn supports_hdr(&self) -> bool {
+        !self.hdr_types.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMetrics {
+    pub physical_size: (u32, u32),
+    pub override_size: Option<(u32, u32)>,
+    pub physical_density: u32,
+    pub override_density: Option<u32>,
+}
+
+impl DisplayMetrics {
+    pub fn size(&self) -> (u32, u32) {
+        self.override_size.unwrap_or(self.physical_size)
+    }
+
+    pub fn density(&self) -> u32 {
+        self.override_density.unwrap_or(self.physical_density)
+    }
+
+    fn parse(size_output: &str, density_output: &str) -> Result<Self, io::Error> {
+        let (physical_size, override_size) = parse_wm_output(size_output, parse_size);
+        let (physical_density, override_density) = parse_wm_output(density_output, |value| value.parse().ok());
+        Ok(DisplayMetrics {
+            physical_size: physical_size.ok_or_else(|| invalid_size(size_output))?,
+            override_size,
+            physical_density: physical_density.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unexpected wm density output: {}", density_output.trim()),
+                )
+            })?,
+            override_density,
+        })
+    }
+}
+
+fn invalid_size(output: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Unexpected wm size output: {}", output.trim()),
+    )
+}
+
+impl ADB {
+    pub fn get_screen_size(&self, device: &str) -> Result<(u32, u32), io::Error> {
+        let output = self.run_adb(&format!("{} shell wm size", device))?;
+        let size = parse_wm_size(&output).ok_or_else(|| invalid_size(&output))?;
+        self.screen_sizes.lock().unwrap().insert(device.to_string(), size);
+        Ok(size)
+    }
+
+    pub async fn get_screen_size_async(&self, device: &str) -> Result<(u32, u32), io::Error> {
+        let output = self.run_adb_async(&format!("{} shell wm size", device)).await?;
+        let size = parse_wm_size(&output).ok_or_else(|| invalid_size(&output))?;
+        self.screen_sizes.lock().unwrap().insert(device.to_string(), size);
+        Ok(size)
+    }
+
+    // Quarter turns from the natural orientation, 0 to 3, as the display is
+    // currently rotated. Falls back to the user rotation setting, which is
+    // only accurate while auto-rotate is off.
+    pub fn get_display_rotation(&self, device: &str) -> Result<u32, io::Error> {
+        let output = self.run_adb(&format!("{} shell dumpsys window displays", device))?;
+        if let Some(rotation) = parse_display_rotation(&output) {
+            return Ok(rotation);
+        }
+        let setting = self.run_adb(&format!("{} shell settings get system user_rotation", device))?;
+        Ok(setting.trim().parse::<u32>().unwrap_or(0) % 4)
+    }
+
+    pub async fn get_display_rotation_async(&self, device: &str) -> Result<u32, io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell dumpsys window displays", device))
+            .await?;
+        if let Some(rotation) = parse_display_rotation(&output) {
+            return Ok(rotation);
+        }
+        let setting = self
+            .run_adb_async(&format!("{} shell settings get system user_rotation", device))
+            .await?;
+        Ok(setting.trim().parse::<u32>().unwrap_or(0) % 4)
+    }
+
+    // The cache holds the natural size, which rotation does not change. The
+    // rotation can change at any time, so it is read on every call; the
+    // `dumpsys window displays` section keeps that a light query.
+    pub(crate) fn screen_size_cached(&self, device: &str) -> Result<(u32, u32), io::Error> {
+        let cached = self.screen_sizes.lock().unwrap().get(device).copied();
+        let size = match cached {
+            Some(size) => size,
+            None => self.get_screen_size(device)?,
+        };
+        Ok(rotate_size(size, self.get_display_rotation(device)?))
+    }
+
+    pub(crate) async fn screen_size_cached_async(&self, device: &str) -> Result<(u32, u32), io::Error> {
+        let cached = self.screen_sizes.lock().unwrap().get(device).copied();
+        let size = match cached {
+            Some(size) => size,
+            None => self.get_screen_size_async(device).await?,
+        };
+        Ok(rotate_size(size, self.get_display_rotation_async(device).await?))
+    }
+
+    pub fn clear_screen_size_cache(&self) {
+        self.screen_sizes.lock().unwrap().clear();
+    }
+
+    pub fn get_display_metrics(&self, device: &str) -> Result<DisplayMetrics, io::Error> {
+        let size = self.run_adb(&format!("{} shell wm size", device))?;
+        let density = self.run_adb(&format!("{} shell wm density", device))?;
+        DisplayMetrics::parse(&size, &density)
+    }
+
+    pub async fn get_display_metrics_async(&self, device: &str) -> Result<DisplayMetrics, io::Error> {
+        let size = self.run_adb_async(&format!("{} shell wm size", device)).await?;
+        let density = self.run_adb_async(&format!("{} shell wm density", device)).await?;
+        DisplayMetrics::parse(&size, &density)
+    }
+
+    pub fn set_display_size(&self, device: &str, width: u32, height: u32) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell wm size {}x{}", device, width, height))?;
+        self.screen_sizes.lock().unwrap().remove(device);
+        Ok(())
+    }
+
+    pub async fn set_display_size_async(&self, device: &str, width: u32, height: u32) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell wm size {}x{}", device, width, height))
+            .await?;
+        self.screen_sizes.lock().unwrap().remove(device);
+        Ok(())
+    }
+
+    pub fn set_display_density(&self, device: &str, dpi: u32) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell wm density {}", device, dpi))?;
+        Ok(())
+    }
+
+    pub async fn set_display_density_async(&self, device: &str, dpi: u32) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell wm density {}", device, dpi))
+            .await?;
+        Ok(())
+    }
+
+    pub fn reset_display(&self, device: &str) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell wm size reset", device))?;
+        self.run_adb(&format!("{} shell wm density reset", device))?;
+        self.screen_sizes.lock().unwrap().remove(device);
+        Ok(())
+    }
+
+    pub async fn reset_display_async(&self, device: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell wm size reset", device))
+            .await?;
+        self.run_adb_async(&format!("{} shell wm density reset", device))
+            .await?;
+        self.screen_sizes.lock().unwrap().remove(device);
+        Ok(())
+    }
+
+    pub fn is_screen_on(&self, device: &str) -> Result<bool, io::Error> {
+        let power = self.run_adb(&format!("{} shell dumpsys power", device))?;
+        if let Some(on) = parse_screen_on(&power) {
+            return Ok(on);
+        }
+        let display = self.run_adb(&format!("{} shell dumpsys display", device))?;
+        parse_display_state(&display).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Screen state not found"))
+    }
+
+    pub async fn is_screen_on_async(&self, device: &str) -> Result<bool, io::Error> {
+        let power = self.run_adb_async(&format!("{} shell dumpsys power", device)).await?;
+        if let Some(on) = parse_screen_on(&power) {
+            return Ok(on);
+        }
+        let display = self.run_adb_async(&format!("{} shell dumpsys display", device)).await?;
+        parse_display_state(&display).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Screen state not found"))
+    }
+
+    pub fn screen_on(&self, device: &str) -> Result<(), io::Error> {
+        self.send_keyevent(device, "KEYCODE_WAKEUP")
+    }
+
+    pub async fn screen_on_async(&self, device: &str) -> Result<(), io::Error> {
+        self.send_keyevent_async(device, "KEYCODE_WAKEUP").await
+    }
+
+    pub fn screen_off(&self, device: &str) -> Result<(), io::Error> {
+        self.send_keyevent(device, "KEYCODE_SLEEP")
+    }
+
+    pub async fn screen_off_async(&self, device: &str) -> Result<(), io::Error> {
+        self.send_keyevent_async(device, "KEYCODE_SLEEP").await
+    }
+
+    // `wm dismiss-keyguard` only works for insecure lock screens; a PIN or
+    // pattern still has to be entered afterwards.
+    pub fn dismiss_keyguard(&self, device: &str) -> Result<(), io::Error> {
+        if !self.is_screen_on(device)? {
+            self.screen_on(device)?;
+        }
+        if self.run_adb(&format!("{} shell wm dismiss-keyguard", device)).is_err() {
+            self.send_keyevent(device, "KEYCODE_MENU")?;
+        }
+        Ok(())
+    }
+
+    pub async fn dismiss_keyguard_async(&self, device: &str) -> Result<(), io::Error> {
+        if !self.is_screen_on_async(device).await? {
+            self.screen_on_async(device).await?;
+        }
+        if self
+            .run_adb_async(&format!("{} shell wm dismiss-keyguard", device))
+            .await
+            .is_err()
+        {
+            self.send_keyevent_async(device, "KEYCODE_MENU").await?;
+        }
+        Ok(())
+    }
+
+    pub fn get_display_info(&self, device: &str) -> Result<DisplayInfo, io::Error> {
+        let output = self.run_adb(&format!("{} shell dumpsys display", device))?;
+        DisplayInfo::parse(&output)
+    }
+
+    pub async fn get_display_info_async(&self, device: &str) -> Result<DisplayInfo, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell dumpsys display", device)).await?;
+        DisplayInfo::parse(&output)
+    }
+}
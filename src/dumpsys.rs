@@ -0,0 +1,57 @@
+// src/dumpsys.rs
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Parses durations in android.util.TimeUtils.formatDuration form, e.g.
+// `-1h2m3s45ms`, `+5s12ms` or `0`. The sign is ignored.
+pub(crate) fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim().trim_start_matches(['-', '+']);
+    if text == "0" {
+        return Some(Duration::ZERO);
+    }
+    let mut total = Duration::ZERO;
+    let mut number = String::new();
+    let mut chars = text.chars().peekable();
+    let mut matched = false;
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let value: u64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            'd' => Duration::from_secs(value * 86_400),
+            'h' => Duration::from_secs(value * 3_600),
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                Duration::from_millis(value)
+            }
+            'm' => Duration::from_secs(value * 60),
+            's' => Duration::from_secs(value),
+            _ => return None,
+        };
+        matched = true;
+    }
+    (matched && number.is_empty()).then_some(total)
+}
+
+// `pm list packages -U` prints `package:<name> uid:<uid>`. Shared uids map
+// to several packages.
+pub(crate) fn parse_package_uids(output: &str) -> HashMap<u32, Vec<String>> {
+    let mut packages: HashMap<u32, Vec<String>> = HashMap::new();
+    for line in output.lines() {
+        let Some(rest) = line.trim().strip_prefix("package:") else {
+            continue;
+        };
+        let Some((name, uid)) = rest.split_once(" uid:") else {
+            continue;
+        };
+        // Some builds append further uids after a comma.
+        for uid in uid.split(',').filter_map(|uid| uid.trim().parse().ok()) {
+            packages.entry(uid).or_default().push(name.to_string());
+        }
+    }
+    packages
+}
@@ -0,0 +1,589 @@
+// src/emulator.rs
+
+use crate::{BatteryHealth, BatteryStatus, ADB};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CONSOLE_TIMEOUT: Duration = Duration::from_secs(5);
+const AUTH_TOKEN_FILE: &str = ".emulator_console_auth_token";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirProtocol {
+    Tcp,
+    Udp,
+}
+
+impl RedirProtocol {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RedirProtocol::Tcp => "tcp",
+            RedirProtocol::Udp => "udp",
+        }
+    }
+}
+
+// Bandwidth of the emulated network, as named by the console's
+// `network speed` presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkSpeed {
+    Gsm,
+    Hscsd,
+    Gprs,
+    Edge,
+    Umts,
+    Hsdpa,
+    Lte,
+    Evdo,
+    Full,
+    Custom { up_kbps: u32, down_kbps: u32 },
+}
+
+impl NetworkSpeed {
+    fn to_arg(self) -> String {
+        match self {
+            NetworkSpeed::Gsm => "gsm".to_string(),
+            NetworkSpeed::Hscsd => "hscsd".to_string(),
+            NetworkSpeed::Gprs => "gprs".to_string(),
+            NetworkSpeed::Edge => "edge".to_string(),
+            NetworkSpeed::Umts => "umts".to_string(),
+            NetworkSpeed::Hsdpa => "hsdpa".to_string(),
+            NetworkSpeed::Lte => "lte".to_string(),
+            NetworkSpeed::Evdo => "evdo".to_string(),
+            NetworkSpeed::Full => "full".to_string(),
+            NetworkSpeed::Custom { up_kbps, down_kbps } => format!("{}:{}", up_kbps, down_kbps),
+        }
+    }
+}
+
+// Latency of the emulated network. Custom delays are picked between
+// `min_ms` and `max_ms` per packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkDelay {
+    None,
+    Gprs,
+    Edge,
+    Umts,
+    Custom { min_ms: u32, max_ms: u32 },
+}
+
+impl NetworkDelay {
+    fn to_arg(self) -> String {
+        match self {
+            NetworkDelay::None => "none".to_string(),
+            NetworkDelay::Gprs => "gprs".to_string(),
+            NetworkDelay::Edge => "edge".to_string(),
+            NetworkDelay::Umts => "umts".to_string(),
+            NetworkDelay::Custom { min_ms, max_ms } => format!("{}:{}", min_ms, max_ms.max(min_ms)),
+        }
+    }
+}
+
+// A host port forwarded into the emulator's virtual network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redirect {
+    pub protocol: RedirProtocol,
+    pub host_port: u16,
+    pub guest_port: u16,
+}
+
+impl Redirect {
+    // `redir list` prints `tcp:5000  => 6000` per redirection.
+    fn parse(line: &str) -> Option<Self> {
+        let (host, guest) = line.split_once("=>")?;
+        let (protocol, host_port) = host.trim().split_once(':')?;
+        let protocol = match protocol {
+            "tcp" => RedirProtocol::Tcp,
+            "udp" => RedirProtocol::Udp,
+            _ => return None,
+        };
+        Some(Redirect {
+            protocol,
+            host_port: host_port.trim().parse().ok()?,
+            guest_port: guest.trim().parse().ok()?,
+        })
+    }
+}
+
+// The emulator console port of a device, from an `emulator-<port>` serial.
+// Accepts the `-s <serial>` form used by device arguments as well.
+pub fn emulator_console_port(device: &str) -> Option<u16> {
+    let serial = device.trim().trim_start_matches("-s").trim();
+    serial.strip_prefix("emulator-")?.parse().ok()
+}
+
+fn console_error(message: &str) -> io::Error {
+    io::Error::other(format!("Emulator console: {}", message))
+}
+
+fn auth_token_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(AUTH_TOKEN_FILE))
+}
+
+// A session with the telnet-style console the emulator serves on
+// localhost. It reaches controls adb cannot, such as sensors, the battery
+// model and port redirection. Every command is answered with its output
+// followed by `OK`, or by `KO: <reason>`.
+pub struct EmulatorConsole {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl EmulatorConsole {
+    // Authenticates with the token the emulator writes to
+    // `~/.emulator_console_auth_token`, when it asks for one.
+    pub fn connect(port: u16) -> Result<Self, io::Error> {
+        let token = match auth_token_path() {
+            Some(path) if path.exists() => Some(fs::read_to_string(path)?.trim().to_string()),
+            _ => None,
+        };
+        Self::connect_with_token(port, token.as_deref())
+    }
+
+    pub fn connect_with_token(port: u16, token: Option<&str>) -> Result<Self, io::Error> {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let stream = TcpStream::connect_timeout(&address, CONSOLE_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONSOLE_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONSOLE_TIMEOUT))?;
+        let mut console = EmulatorConsole {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+
+        let banner = console.read_reply()?;
+        if banner.contains("Authentication required") {
+            let token = token.filter(|token| !token.is_empty()).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("Emulator console on port {} requires an auth token (~/{})", port, AUTH_TOKEN_FILE),
+                )
+            })?;
+            console.command(&format!("auth {}", token)).map_err(|error| {
+                io::Error::new(io::ErrorKind::PermissionDenied, error.to_string())
+            })?;
+        }
+        Ok(console)
+    }
+
+    // Connects to the console of an `emulator-<port>` device.
+    pub fn for_device(device: &str) -> Result<Self, io::Error> {
+        let port = emulator_console_port(device).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not an emulator serial", device.trim()),
+            )
+        })?;
+        Self::connect(port)
+    }
+
+    // Collects lines up to the closing `OK`, turning `KO` into an error.
+    fn read_reply(&mut self) -> Result<String, io::Error> {
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Emulator console closed the connection"));
+            }
+            let line = line.trim_end();
+            if line == "OK" {
+                return Ok(output);
+            }
+            if let Some(reason) = line.strip_prefix("KO") {
+                return Err(console_error(reason.trim_start_matches(':').trim()));
+            }
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+
+    // Sends a raw console command and returns its output without the
+    // closing `OK`.
+    pub fn command(&mut self, command: &str) -> Result<String, io::Error> {
+        self.writer.write_all(format!("{}\n", command.trim()).as_bytes())?;
+        self.writer.flush()?;
+        self.read_reply()
+    }
+
+    pub fn avd_name(&mut self) -> Result<String, io::Error> {
+        Ok(self.command("avd name")?.trim().to_string())
+    }
+
+    // Rotates the screen 90 degrees counter-clockwise.
+    pub fn rotate(&mut self) -> Result<(), io::Error> {
+        self.command("rotate")?;
+        Ok(())
+    }
+
+    pub fn set_ac_charging(&mut self, connected: bool) -> Result<(), io::Error> {
+        self.command(&format!("power ac {}", if connected { "on" } else { "off" }))?;
+        Ok(())
+    }
+
+    pub fn set_battery_capacity(&mut self, percent: u8) -> Result<(), io::Error> {
+        self.command(&format!("power capacity {}", percent.min(100)))?;
+        Ok(())
+    }
+
+    pub fn set_battery_status(&mut self, status: BatteryStatus) -> Result<(), io::Error> {
+        let status = match status {
+            BatteryStatus::Unknown => "unknown",
+            BatteryStatus::Charging => "charging",
+            BatteryStatus::Discharging => "discharging",
+            BatteryStatus::NotCharging => "not-charging",
+            BatteryStatus::Full => "full",
+        };
+        self.command(&format!("power status {}", status))?;
+        Ok(())
+    }
+
+    pub fn set_battery_health(&mut self, health: BatteryHealth) -> Result<(), io::Error> {
+        let health = match health {
+            BatteryHealth::Unknown => "unknown",
+            BatteryHealth::Good => "good",
+            BatteryHealth::Overheat => "overheat",
+            BatteryHealth::Dead => "dead",
+            BatteryHealth::OverVoltage => "overvoltage",
+            BatteryHealth::Failure => "failure",
+            BatteryHealth::Cold => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "The emulator battery model has no cold health state",
+                ))
+            }
+        };
+        self.command(&format!("power health {}", health))?;
+        Ok(())
+    }
+
+    fn set_battery(&mut self, level: u8, ac_connected: bool, health: BatteryHealth) -> Result<(), io::Error> {
+        let status = match (ac_connected, level >= 100) {
+            (true, true) => BatteryStatus::Full,
+            (true, false) => BatteryStatus::Charging,
+            (false, _) => BatteryStatus::Discharging,
+        };
+        self.set_battery_health(health)?;
+        self.set_ac_charging(ac_connected)?;
+        self.set_battery_status(status)?;
+        self.set_battery_capacity(level)
+    }
+
+    // Sensor names as listed by `sensor status`, e.g. `acceleration`,
+    // `magnetic-field`, `orientation`, `temperature` or `proximity`.
+    pub fn get_sensor(&mut self, name: &str) -> Result<Vec<f32>, io::Error> {
+        // Replies look like `acceleration = 0:9.81:0`.
+        let output = self.command(&format!("sensor get {}", name))?;
+        let values = output
+            .rsplit_once('=')
+            .map_or(output.as_str(), |(_, values)| values)
+            .trim();
+        values
+            .split(':')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| console_error(&format!("unexpected sensor reply {:?}", output.trim())))
+            })
+            .collect()
+    }
+
+    pub fn set_sensor(&mut self, name: &str, values: &[f32]) -> Result<(), io::Error> {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        self.command(&format!("sensor set {} {}", name, values.join(":")))?;
+        Ok(())
+    }
+
+    pub fn add_redirect(&mut self, redirect: Redirect) -> Result<(), io::Error> {
+        self.command(&format!(
+            "redir add {}:{}:{}",
+            redirect.protocol.as_str(),
+            redirect.host_port,
+            redirect.guest_port
+        ))?;
+        Ok(())
+    }
+
+    pub fn remove_redirect(&mut self, protocol: RedirProtocol, host_port: u16) -> Result<(), io::Error> {
+        self.command(&format!("redir del {}:{}", protocol.as_str(), host_port))?;
+        Ok(())
+    }
+
+    pub fn list_redirects(&mut self) -> Result<Vec<Redirect>, io::Error> {
+        Ok(self.command("redir list")?.lines().filter_map(Redirect::parse).collect())
+    }
+
+    // Note that `geo fix` itself takes the longitude first.
+    pub fn geo_fix(&mut self, latitude: f64, longitude: f64, altitude: Option<f64>) -> Result<(), io::Error> {
+        let mut command = format!("geo fix {} {}", longitude, latitude);
+        if let Some(altitude) = altitude {
+            command.push_str(&format!(" {}", altitude));
+        }
+        self.command(&command)?;
+        Ok(())
+    }
+
+    // The console reads one command per line, so the text has to fit on one.
+    pub fn send_sms(&mut self, from: &str, text: &str) -> Result<(), io::Error> {
+        if text.contains(['\n', '\r']) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "SMS text must be a single line"));
+        }
+        self.command(&format!("sms send {} {}", from.trim(), text))?;
+        Ok(())
+    }
+
+    pub fn set_network_speed(&mut self, speed: NetworkSpeed) -> Result<(), io::Error> {
+        self.command(&format!("network speed {}", speed.to_arg()))?;
+        Ok(())
+    }
+
+    pub fn set_network_delay(&mut self, delay: NetworkDelay) -> Result<(), io::Error> {
+        self.command(&format!("network delay {}", delay.to_arg()))?;
+        Ok(())
+    }
+
+    // Raw GSM signal strength: an RSSI of 0 to 31, 99 meaning unknown.
+    pub fn gsm_signal(&mut self, rssi: u8) -> Result<(), io::Error> {
+        let rssi = if rssi == 99 { rssi } else { rssi.min(31) };
+        self.command(&format!("gsm signal {}", rssi))?;
+        Ok(())
+    }
+
+    // Signal level from 0 (none) to 4 (great), as shown in the status bar.
+    pub fn gsm_signal_profile(&mut self, level: u8) -> Result<(), io::Error> {
+        self.command(&format!("gsm signal-profile {}", level.min(4)))?;
+        Ok(())
+    }
+
+    // Rings the emulator with an incoming call from `number`.
+    pub fn gsm_call(&mut self, number: &str) -> Result<(), io::Error> {
+        self.command(&format!("gsm call {}", number.trim()))?;
+        Ok(())
+    }
+
+    // Hangs up the call with `number`, from the remote side.
+    pub fn gsm_cancel(&mut self, number: &str) -> Result<(), io::Error> {
+        self.command(&format!("gsm cancel {}", number.trim()))?;
+        Ok(())
+    }
+}
+
+// Console sessions block, so async callers run them on the blocking pool.
+pub(crate) async fn with_console_async<T, F>(device: &str, action: F) -> Result<T, io::Error>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut EmulatorConsole) -> Result<T, io::Error> + Send + 'static,
+{
+    let device = device.to_string();
+    tokio::task::spawn_blocking(move || action(&mut EmulatorConsole::for_device(&device)?))
+        .await
+        .map_err(io::Error::other)?
+}
+
+// An emulator whose console is reachable, bundling its device argument with
+// the console-backed controls.
+#[derive(Clone)]
+pub struct EmulatorHandle {
+    adb: ADB,
+    device: String,
+    port: u16,
+}
+
+impl EmulatorHandle {
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    pub fn adb(&self) -> &ADB {
+        &self.adb
+    }
+
+    pub fn console_port(&self) -> u16 {
+        self.port
+    }
+
+    // A fresh console session, for commands without a helper here.
+    pub fn console(&self) -> Result<EmulatorConsole, io::Error> {
+        EmulatorConsole::connect(self.port)
+    }
+
+    pub fn set_location(&self, latitude: f64, longitude: f64, altitude: Option<f64>) -> Result<(), io::Error> {
+        self.adb.set_emulator_geo_fix(&self.device, latitude, longitude, altitude)
+    }
+
+    pub async fn set_location_async(&self, latitude: f64, longitude: f64, altitude: Option<f64>) -> Result<(), io::Error> {
+        self.adb
+            .set_emulator_geo_fix_async(&self.device, latitude, longitude, altitude)
+            .await
+    }
+
+    pub fn send_sms(&self, from: &str, text: &str) -> Result<(), io::Error> {
+        self.adb.send_emulator_sms(&self.device, from, text)
+    }
+
+    pub async fn send_sms_async(&self, from: &str, text: &str) -> Result<(), io::Error> {
+        self.adb.send_emulator_sms_async(&self.device, from, text).await
+    }
+
+    pub fn simulate_incoming_call(&self, number: &str) -> Result<(), io::Error> {
+        self.adb.simulate_incoming_call(&self.device, number)
+    }
+
+    pub async fn simulate_incoming_call_async(&self, number: &str) -> Result<(), io::Error> {
+        self.adb.simulate_incoming_call_async(&self.device, number).await
+    }
+
+    pub fn end_call(&self, number: &str) -> Result<(), io::Error> {
+        self.adb.end_call(&self.device, number)
+    }
+
+    pub async fn end_call_async(&self, number: &str) -> Result<(), io::Error> {
+        self.adb.end_call_async(&self.device, number).await
+    }
+
+    pub fn set_network(&self, speed: NetworkSpeed, delay: NetworkDelay) -> Result<(), io::Error> {
+        self.adb.set_emulator_network(&self.device, speed, delay)
+    }
+
+    pub async fn set_network_async(&self, speed: NetworkSpeed, delay: NetworkDelay) -> Result<(), io::Error> {
+        self.adb.set_emulator_network_async(&self.device, speed, delay).await
+    }
+
+    pub fn set_signal_strength(&self, level: u8) -> Result<(), io::Error> {
+        self.adb.set_signal_strength(&self.device, level)
+    }
+
+    pub async fn set_signal_strength_async(&self, level: u8) -> Result<(), io::Error> {
+        self.adb.set_signal_strength_async(&self.device, level).await
+    }
+
+    pub fn set_battery(&self, level: u8, ac_connected: bool, health: BatteryHealth) -> Result<(), io::Error> {
+        self.adb.set_emulator_battery(&self.device, level, ac_connected, health)
+    }
+
+    pub async fn set_battery_async(&self, level: u8, ac_connected: bool, health: BatteryHealth) -> Result<(), io::Error> {
+        self.adb
+            .set_emulator_battery_async(&self.device, level, ac_connected, health)
+            .await
+    }
+}
+
+fn is_qemu(kernel_qemu: &str, boot_qemu: &str) -> bool {
+    kernel_qemu.trim() == "1" || boot_qemu.trim() == "1"
+}
+
+impl ADB {
+    // Emulators started locally carry an `emulator-<port>` serial. Ones
+    // reached through `adb connect` do not, but still set the qemu props.
+    pub fn is_emulator(&self, device: &str) -> Result<bool, io::Error> {
+        if emulator_console_port(device).is_some() {
+            return Ok(true);
+        }
+        Ok(is_qemu(
+            &self.get_prop(device, "ro.kernel.qemu")?,
+            &self.get_prop(device, "ro.boot.qemu")?,
+        ))
+    }
+
+    pub async fn is_emulator_async(&self, device: &str) -> Result<bool, io::Error> {
+        if emulator_console_port(device).is_some() {
+            return Ok(true);
+        }
+        Ok(is_qemu(
+            &self.get_prop_async(device, "ro.kernel.qemu").await?,
+            &self.get_prop_async(device, "ro.boot.qemu").await?,
+        ))
+    }
+
+    // None for physical devices, and for emulators without a console port
+    // in their serial, such as those reached through `adb connect`.
+    pub fn emulator_handle(&self, device: &str) -> Option<EmulatorHandle> {
+        Some(EmulatorHandle {
+            adb: self.clone(),
+            device: device.to_string(),
+            port: emulator_console_port(device)?,
+        })
+    }
+
+    pub fn send_emulator_sms(&self, emulator: &str, from: &str, text: &str) -> Result<(), io::Error> {
+        EmulatorConsole::for_device(emulator)?.send_sms(from, text)
+    }
+
+    pub async fn send_emulator_sms_async(&self, emulator: &str, from: &str, text: &str) -> Result<(), io::Error> {
+        let (from, text) = (from.to_string(), text.to_string());
+        with_console_async(emulator, move |console| console.send_sms(&from, &text)).await
+    }
+
+    // Throttles the emulator's network the way the console presets model
+    // cellular links. Physical devices have no equivalent in adb.
+    pub fn set_emulator_network(&self, emulator: &str, speed: NetworkSpeed, delay: NetworkDelay) -> Result<(), io::Error> {
+        let mut console = EmulatorConsole::for_device(emulator)?;
+        console.set_network_speed(speed)?;
+        console.set_network_delay(delay)
+    }
+
+    pub async fn set_emulator_network_async(
+        &self,
+        emulator: &str,
+        speed: NetworkSpeed,
+        delay: NetworkDelay,
+    ) -> Result<(), io::Error> {
+        with_console_async(emulator, move |console| {
+            console.set_network_speed(speed)?;
+            console.set_network_delay(delay)
+        })
+        .await
+    }
+
+    // `level` runs from 0 (none) to 4 (great), matching
+    // `TelephonyInfo::signal_level`.
+    pub fn set_signal_strength(&self, emulator: &str, level: u8) -> Result<(), io::Error> {
+        EmulatorConsole::for_device(emulator)?.gsm_signal_profile(level)
+    }
+
+    pub async fn set_signal_strength_async(&self, emulator: &str, level: u8) -> Result<(), io::Error> {
+        with_console_async(emulator, move |console| console.gsm_signal_profile(level)).await
+    }
+
+    // Sets the emulated battery in one go. The charging status follows
+    // `ac_connected`, reading full at 100%.
+    pub fn set_emulator_battery(
+        &self,
+        emulator: &str,
+        level: u8,
+        ac_connected: bool,
+        health: BatteryHealth,
+    ) -> Result<(), io::Error> {
+        EmulatorConsole::for_device(emulator)?.set_battery(level, ac_connected, health)
+    }
+
+    pub async fn set_emulator_battery_async(
+        &self,
+        emulator: &str,
+        level: u8,
+        ac_connected: bool,
+        health: BatteryHealth,
+    ) -> Result<(), io::Error> {
+        with_console_async(emulator, move |console| console.set_battery(level, ac_connected, health)).await
+    }
+
+    pub fn simulate_incoming_call(&self, emulator: &str, number: &str) -> Result<(), io::Error> {
+        EmulatorConsole::for_device(emulator)?.gsm_call(number)
+    }
+
+    pub async fn simulate_incoming_call_async(&self, emulator: &str, number: &str) -> Result<(), io::Error> {
+        let number = number.to_string();
+        with_console_async(emulator, move |console| console.gsm_call(&number)).await
+    }
+
+    pub fn end_call(&self, emulator: &str, number: &str) -> Result<(), io::Error> {
+        EmulatorConsole::for_device(emulator)?.gsm_cancel(number)
+    }
+
+    pub async fn end_call_async(&self, emulator: &str, number: &str) -> Result<(), io::Error> {
+        let number = number.to_string();
+        with_console_async(emulator, move |console| console.gsm_cancel(&number)).await
+    }
+}
@@ -0,0 +1,258 @@
+// src/forward.rs
+
+use crate::ADB;
+use std::fmt;
+use std::io;
+use std::net::TcpListener;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ForwardTarget {
+    Tcp(u16),
+    LocalAbstract(String),
+    LocalReserved(String),
+    LocalFilesystem(String),
+    Dev(String),
+    Jdwp(u32),
+}
+
+impl fmt::Display for ForwardTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForwardTarget::Tcp(port) => write!(f, "tcp:{}", port),
+            ForwardTarget::LocalAbstract(name) => write!(f, "localabstract:{}", name),
+            ForwardTarget::LocalReserved(name) => write!(f, "localreserved:{}", name),
+            ForwardTarget::LocalFilesystem(path) => write!(f, "localfilesystem:{}", path),
+            ForwardTarget::Dev(path) => write!(f, "dev:{}", path),
+            ForwardTarget::Jdwp(pid) => write!(f, "jdwp:{}", pid),
+        }
+    }
+}
+
+impl FromStr for ForwardTarget {
+    type Err = io::Error;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("Unknown forward spec: {}", spec));
+        let (kind, value) = spec.split_once(':').ok_or_else(invalid)?;
+        Ok(match kind {
+            "tcp" => ForwardTarget::Tcp(value.parse().map_err(|_| invalid())?),
+            "localabstract" => ForwardTarget::LocalAbstract(value.to_string()),
+            "localreserved" => ForwardTarget::LocalReserved(value.to_string()),
+            "localfilesystem" => ForwardTarget::LocalFilesystem(value.to_string()),
+            "dev" => ForwardTarget::Dev(value.to_string()),
+            "jdwp" => ForwardTarget::Jdwp(value.parse().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardRule {
+    pub serial: String,
+    pub local: ForwardTarget,
+    pub remote: ForwardTarget,
+}
+
+// Asks the OS for a free port. The port is released before adb binds it, so
+// this is only the fallback for adb versions without `tcp:0` support.
+fn probe_free_port() -> Result<u16, io::Error> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+// For `adb reverse` the listening side is on the device and connections are
+// relayed to the host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReverseRule {
+    pub transport: String,
+    pub device: ForwardTarget,
+    pub host: ForwardTarget,
+}
+
+fn parse_rule_fields(line: &str) -> Result<(String, ForwardTarget, ForwardTarget), io::Error> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields[..] {
+        [name, first, second] => Ok((name.to_string(), first.parse()?, second.parse()?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unexpected forward list line: {}", line),
+        )),
+    }
+}
+
+// `forward --list` prints `<serial> <local> <remote>` per rule for every
+// connected device, even with `-s`, so rules are kept only for the serial
+// in `device` when it names one. Lines with specs this crate does not know,
+// such as newer `vsock:` ones, are skipped.
+fn parse_forward_list(output: &str, device: &str) -> Vec<ForwardRule> {
+    let serial = device
+        .trim()
+        .strip_prefix("-s")
+        .map(str::trim)
+        .filter(|serial| !serial.is_empty());
+    output
+        .lines()
+        .filter_map(|line| parse_rule_fields(line).ok())
+        .filter(|(rule_serial, _, _)| serial.is_none_or(|serial| rule_serial == serial))
+        .map(|(serial, local, remote)| ForwardRule { serial, local, remote })
+        .collect()
+}
+
+// `reverse --list` prints `<transport> <device side> <host side>`.
+fn parse_reverse_list(output: &str) -> Vec<ReverseRule> {
+    output
+        .lines()
+        .filter_map(|line| parse_rule_fields(line).ok())
+        .map(|(transport, device, host)| ReverseRule { transport, device, host })
+        .collect()
+}
+
+impl ADB {
+    pub fn forward(&self, device: &str, local: &ForwardTarget, remote: &ForwardTarget) -> Result<String, io::Error> {
+        self.run_adb(&format!("{} forward {} {}", device, local, remote))
+    }
+
+    pub async fn forward_async(&self, device: &str, local: &ForwardTarget, remote: &ForwardTarget) -> Result<String, io::Error> {
+        self.run_adb_async(&format!("{} forward {} {}", device, local, remote)).await
+    }
+
+    pub fn list_forwards(&self, device: &str) -> Result<Vec<ForwardRule>, io::Error> {
+        let output = self.run_adb(&format!("{} forward --list", device))?;
+        Ok(parse_forward_list(&output, device))
+    }
+
+    pub async fn list_forwards_async(&self, device: &str) -> Result<Vec<ForwardRule>, io::Error> {
+        let output = self.run_adb_async(&format!("{} forward --list", device)).await?;
+        Ok(parse_forward_list(&output, device))
+    }
+
+    pub fn remove_forward(&self, device: &str, local: &ForwardTarget) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} forward --remove {}", device, local))?;
+        Ok(())
+    }
+
+    pub async fn remove_forward_async(&self, device: &str, local: &ForwardTarget) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} forward --remove {}", device, local))
+            .await?;
+        Ok(())
+    }
+
+    pub fn remove_all_forwards(&self, device: &str) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} forward --remove-all", device))?;
+        Ok(())
+    }
+
+    pub async fn remove_all_forwards_async(&self, device: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} forward --remove-all", device))
+            .await?;
+        Ok(())
+    }
+
+    // On-device agents (uiautomator servers, scrcpy, Frida) commonly listen
+    // on abstract sockets rather than TCP ports.
+    pub fn forward_to_abstract_socket(&self, device: &str, local_port: u16, socket_name: &str) -> Result<String, io::Error> {
+        self.forward(
+            device,
+            &ForwardTarget::Tcp(local_port),
+            &ForwardTarget::LocalAbstract(socket_name.to_string()),
+        )
+    }
+
+    pub async fn forward_to_abstract_socket_async(&self, device: &str, local_port: u16, socket_name: &str) -> Result<String, io::Error> {
+        self.forward_async(
+            device,
+            &ForwardTarget::Tcp(local_port),
+            &ForwardTarget::LocalAbstract(socket_name.to_string()),
+        )
+        .await
+    }
+
+    pub fn reverse(&self, device: &str, device_side: &ForwardTarget, host_side: &ForwardTarget) -> Result<String, io::Error> {
+        self.run_adb(&format!("{} reverse {} {}", device, device_side, host_side))
+    }
+
+    pub async fn reverse_async(&self, device: &str, device_side: &ForwardTarget, host_side: &ForwardTarget) -> Result<String, io::Error> {
+        self.run_adb_async(&format!("{} reverse {} {}", device, device_side, host_side))
+            .await
+    }
+
+    // Lets a device process connect to `socket_name` and reach `local_port`
+    // on the host.
+    pub fn reverse_from_abstract_socket(&self, device: &str, socket_name: &str, local_port: u16) -> Result<String, io::Error> {
+        self.reverse(
+            device,
+            &ForwardTarget::LocalAbstract(socket_name.to_string()),
+            &ForwardTarget::Tcp(local_port),
+        )
+    }
+
+    pub async fn reverse_from_abstract_socket_async(&self, device: &str, socket_name: &str, local_port: u16) -> Result<String, io::Error> {
+        self.reverse_async(
+            device,
+            &ForwardTarget::LocalAbstract(socket_name.to_string()),
+            &ForwardTarget::Tcp(local_port),
+        )
+        .await
+    }
+
+    pub fn list_reverses(&self, device: &str) -> Result<Vec<ReverseRule>, io::Error> {
+        let output = self.run_adb(&format!("{} reverse --list", device))?;
+        Ok(parse_reverse_list(&output))
+    }
+
+    pub async fn list_reverses_async(&self, device: &str) -> Result<Vec<ReverseRule>, io::Error> {
+        let output = self.run_adb_async(&format!("{} reverse --list", device)).await?;
+        Ok(parse_reverse_list(&output))
+    }
+
+    pub fn remove_reverse(&self, device: &str, device_side: &ForwardTarget) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} reverse --remove {}", device, device_side))?;
+        Ok(())
+    }
+
+    pub async fn remove_reverse_async(&self, device: &str, device_side: &ForwardTarget) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} reverse --remove {}", device, device_side))
+            .await?;
+        Ok(())
+    }
+
+    pub fn remove_all_reverses(&self, device: &str) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} reverse --remove-all", device))?;
+        Ok(())
+    }
+
+    pub async fn remove_all_reverses_async(&self, device: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} reverse --remove-all", device))
+            .await?;
+        Ok(())
+    }
+
+    // Forwards `tcp:0`, for which adb picks a free local port and prints it.
+    // Older adb versions reject `tcp:0` or print nothing, and get a probed
+    // port instead.
+    pub fn forward_port_auto(&self, device: &str, remote: &ForwardTarget) -> Result<u16, io::Error> {
+        if let Ok(output) = self.forward(device, &ForwardTarget::Tcp(0), remote) {
+            if let Ok(port) = output.trim().parse() {
+                return Ok(port);
+            }
+            // Fails when no `tcp:0` rule was left behind, which is fine.
+            let _ = self.remove_forward(device, &ForwardTarget::Tcp(0));
+        }
+        let port = probe_free_port()?;
+        self.forward(device, &ForwardTarget::Tcp(port), remote)?;
+        Ok(port)
+    }
+
+    pub async fn forward_port_auto_async(&self, device: &str, remote: &ForwardTarget) -> Result<u16, io::Error> {
+        if let Ok(output) = self.forward_async(device, &ForwardTarget::Tcp(0), remote).await {
+            if let Ok(port) = output.trim().parse() {
+                return Ok(port);
+            }
+            let _ = self.remove_forward_async(device, &ForwardTarget::Tcp(0)).await;
+        }
+        let port = probe_free_port()?;
+        self.forward_async(device, &ForwardTarget::Tcp(port), remote)
+            .await?;
+        Ok(port)
+    }
+}
@@ -0,0 +1,206 @@
+// src/hardware.rs
+
+use crate::ADB;
+use std::collections::BTreeMap;
+use std::io;
+
+// Prints one `<core> <field> <value>` line per sysfs attribute so a single
+// round-trip covers every core.
+const CPU_INFO_SCRIPT: &str = "for c in /sys/devices/system/cpu/cpu[0-9]*; do \
+    n=${c##*/cpu}; \
+    echo \"$n online $(cat $c/online 2>/dev/null)\"; \
+    echo \"$n cur $(cat $c/cpufreq/scaling_cur_freq 2>/dev/null)\"; \
+    echo \"$n min $(cat $c/cpufreq/scaling_min_freq 2>/dev/null)\"; \
+    echo \"$n max $(cat $c/cpufreq/scaling_max_freq 2>/dev/null)\"; \
+    echo \"$n governor $(cat $c/cpufreq/scaling_governor 2>/dev/null)\"; \
+    done";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuCore {
+    pub id: u32,
+    pub online: bool,
+    pub cur_freq_khz: Option<u64>,
+    pub min_freq_khz: Option<u64>,
+    pub max_freq_khz: Option<u64>,
+    pub governor: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuInfo {
+    pub cores: Vec<CpuCore>,
+}
+
+impl CpuInfo {
+    pub fn core_count(&self) -> usize {
+        self.cores.len()
+    }
+
+    pub fn online_count(&self) -> usize {
+        self.cores.iter().filter(|core| core.online).count()
+    }
+
+    fn parse(output: &str) -> Self {
+        let mut cores: BTreeMap<u32, CpuCore> = BTreeMap::new();
+        for line in output.lines() {
+            let mut parts = line.splitn(3, ' ');
+            let (Some(id), Some(field)) = (parts.next(), parts.next()) else {
+                continue;
+            };
+            let Ok(id) = id.parse() else {
+                continue;
+            };
+            let value = parts.next().unwrap_or("").trim();
+            let core = cores.entry(id).or_insert_with(|| CpuCore {
+                id,
+                // cpu0 usually has no `online` attribute because it can't be
+                // hot-unplugged.
+                online: true,
+                ..CpuCore::default()
+            });
+            match field {
+                "online" if !value.is_empty() => core.online = value == "1",
+                "cur" => core.cur_freq_khz = value.parse().ok(),
+                "min" => core.min_freq_khz = value.parse().ok(),
+                "max" => core.max_freq_khz = value.parse().ok(),
+                "governor" if !value.is_empty() => core.governor = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        CpuInfo {
+            cores: cores.into_values().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GpuInfo {
+    pub vendor: Option<String>,
+    pub renderer: Option<String>,
+    pub gl_version: Option<String>,
+    pub gles_version: Option<(u32, u32)>,
+    pub vulkan_version: Option<(u32, u32, u32)>,
+    pub vulkan_driver: Option<String>,
+    pub egl_driver: Option<String>,
+}
+
+impl GpuInfo {
+    // `surface_flinger` is `dumpsys SurfaceFlinger`, whose `GLES:` line reads
+    // `GLES: <vendor>, <renderer>, <version>`; `features` is `pm list features`.
+    fn parse(surface_flinger: &str, features: &str, gles_prop: &str, vulkan_prop: &str, egl_prop: &str) -> Self {
+        let mut info = GpuInfo::default();
+
+        if let Some(gles) = surface_flinger
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("GLES:"))
+        {
+            let mut parts = gles.splitn(3, ',').map(|part| part.trim().to_string());
+            info.vendor = parts.next().filter(|part| !part.is_empty());
+            info.renderer = parts.next().filter(|part| !part.is_empty());
+            info.gl_version = parts.next().filter(|part| !part.is_empty());
+        }
+
+        // ro.opengles.version packs major.minor as 0xMMMMmmmm.
+        if let Ok(packed) = gles_prop.trim().parse::<u32>() {
+            info.gles_version = Some((packed >> 16, packed & 0xffff));
+        }
+
+        // Packed with VK_MAKE_VERSION: 10 bits major, 10 bits minor, 12 bits patch.
+        info.vulkan_version = features
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("feature:android.hardware.vulkan.version="))
+            .filter_map(|value| value.parse::<u32>().ok())
+            .max()
+            .map(|packed| (packed >> 22, (packed >> 12) & 0x3ff, packed & 0xfff));
+
+        info.vulkan_driver = Some(vulkan_prop.trim().to_string()).filter(|value| !value.is_empty());
+        info.egl_driver = Some(egl_prop.trim().to_string()).filter(|value| !value.is_empty());
+        info
+    }
+
+    pub fn supports_vulkan(&self) -> bool {
+        self.vulkan_version.is_some()
+    }
+}
+
+fn set_governor_command(device: &str, governor: &str) -> String {
+    format!(
+        "{} shell su -c \"for f in /sys/devices/system/cpu/cpu[0-9]*/cpufreq/scaling_governor; do echo {} > $f; done\"",
+        device, governor
+    )
+}
+
+fn check_governor(info: &CpuInfo, governor: &str) -> Result<(), io::Error> {
+    let unchanged: Vec<String> = info
+        .cores
+        .iter()
+        .filter(|core| core.online && core.governor.as_deref() != Some(governor))
+        .map(|core| core.id.to_string())
+        .collect();
+    if unchanged.is_empty() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "Governor {} not applied to cpu {} (device not rooted or governor unsupported)",
+                governor,
+                unchanged.join(", ")
+            ),
+        ))
+    }
+}
+
+impl ADB {
+    pub fn get_cpu_info(&self, device: &str) -> Result<CpuInfo, io::Error> {
+        let output = self.run_adb(&format!("{} shell {}", device, CPU_INFO_SCRIPT))?;
+        Ok(CpuInfo::parse(&output))
+    }
+
+    pub async fn get_cpu_info_async(&self, device: &str) -> Result<CpuInfo, io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell {}", device, CPU_INFO_SCRIPT))
+            .await?;
+        Ok(CpuInfo::parse(&output))
+    }
+
+    // Requires root. The result is read back so a silently ignored write
+    // surfaces as an error.
+    pub fn set_cpu_governor(&self, device: &str, governor: &str) -> Result<(), io::Error> {
+        self.run_adb(&set_governor_command(device, governor))?;
+        check_governor(&self.get_cpu_info(device)?, governor)
+    }
+
+    pub async fn set_cpu_governor_async(&self, device: &str, governor: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&set_governor_command(device, governor))
+            .await?;
+        check_governor(&self.get_cpu_info_async(device).await?, governor)
+    }
+
+    pub fn get_gpu_info(&self, device: &str) -> Result<GpuInfo, io::Error> {
+        let surface_flinger = self.run_adb(&format!("{} shell dumpsys SurfaceFlinger", device))?;
+        let features = self.run_adb(&format!("{} shell pm list features", device))?;
+        Ok(GpuInfo::parse(
+            &surface_flinger,
+            &features,
+            &self.get_prop(device, "ro.opengles.version")?,
+            &self.get_prop(device, "ro.hardware.vulkan")?,
+            &self.get_prop(device, "ro.hardware.egl")?,
+        ))
+    }
+
+    pub async fn get_gpu_info_async(&self, device: &str) -> Result<GpuInfo, io::Error> {
+        let surface_flinger = self
+            .run_adb_async(&format!("{} shell dumpsys SurfaceFlinger", device))
+            .await?;
+        let features = self
+            .run_adb_async(&format!("{} shell pm list features", device))
+            .await?;
+        Ok(GpuInfo::parse(
+            &surface_flinger,
+            &features,
+            &self.get_prop_async(device, "ro.opengles.version").await?,
+            &self.get_prop_async(device, "ro.hardware.vulkan").await?,
+            &self.get_prop_async(device, "ro.hardware.egl").await?,
+        ))
+    }
+}
@@ -0,0 +1,123 @@
+// src/ime.rs
+
+use crate::ADB;
+use std::io;
+
+fn parse_ids(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Restores the IME that was active before `swap_input_method` when dropped.
+// Dropping runs a blocking adb call and ignores failures; call `restore` or
+// `restore_async` to see them.
+pub struct InputMethodGuard<'a> {
+    adb: &'a ADB,
+    device: String,
+    previous: Option<String>,
+}
+
+impl InputMethodGuard<'_> {
+    pub fn previous(&self) -> Option<&str> {
+        self.previous.as_deref()
+    }
+
+    pub fn restore(mut self) -> Result<(), io::Error> {
+        match self.previous.take() {
+            Some(previous) => self.adb.set_input_method(&self.device, &previous),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn restore_async(mut self) -> Result<(), io::Error> {
+        match self.previous.take() {
+            Some(previous) => self.adb.set_input_method_async(&self.device, &previous).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for InputMethodGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            // Errors have nowhere to go here; `restore` reports them.
+            let _ = self.adb.set_input_method(&self.device, &previous);
+        }
+    }
+}
+
+impl ADB {
+    pub fn list_input_methods(&self, device: &str, enabled_only: bool) -> Result<Vec<String>, io::Error> {
+        let flag = if enabled_only { "-s" } else { "-a -s" };
+        let output = self.run_adb(&format!("{} shell ime list {}", device, flag))?;
+        Ok(parse_ids(&output))
+    }
+
+    pub async fn list_input_methods_async(&self, device: &str, enabled_only: bool) -> Result<Vec<String>, io::Error> {
+        let flag = if enabled_only { "-s" } else { "-a -s" };
+        let output = self
+            .run_adb_async(&format!("{} shell ime list {}", device, flag))
+            .await?;
+        Ok(parse_ids(&output))
+    }
+
+    pub fn get_input_method(&self, device: &str) -> Result<String, io::Error> {
+        let output = self.run_adb(&format!("{} shell settings get secure default_input_method", device))?;
+        Ok(output.trim().to_string())
+    }
+
+    pub async fn get_input_method_async(&self, device: &str) -> Result<String, io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell settings get secure default_input_method", device))
+            .await?;
+        Ok(output.trim().to_string())
+    }
+
+    pub fn enable_input_method(&self, device: &str, ime_id: &str) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell ime enable {}", device, ime_id))?;
+        Ok(())
+    }
+
+    pub async fn enable_input_method_async(&self, device: &str, ime_id: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell ime enable {}", device, ime_id))
+            .await?;
+        Ok(())
+    }
+
+    pub fn set_input_method(&self, device: &str, ime_id: &str) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell ime set {}", device, ime_id))?;
+        Ok(())
+    }
+
+    pub async fn set_input_method_async(&self, device: &str, ime_id: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell ime set {}", device, ime_id))
+            .await?;
+        Ok(())
+    }
+
+    pub fn swap_input_method(&self, device: &str, ime_id: &str) -> Result<InputMethodGuard<'_>, io::Error> {
+        let previous = self.get_input_method(device)?;
+        self.enable_input_method(device, ime_id)?;
+        self.set_input_method(device, ime_id)?;
+        Ok(InputMethodGuard {
+            adb: self,
+            device: device.to_string(),
+            previous: Some(previous).filter(|id| !id.is_empty() && id != "null"),
+        })
+    }
+
+    pub async fn swap_input_method_async(&self, device: &str, ime_id: &str) -> Result<InputMethodGuard<'_>, io::Error> {
+        let previous = self.get_input_method_async(device).await?;
+        self.enable_input_method_async(device, ime_id).await?;
+        self.set_input_method_async(device, ime_id).await?;
+        Ok(InputMethodGuard {
+            adb: self,
+            device: device.to_string(),
+            previous: Some(previous).filter(|id| !id.is_empty() && id != "null"),
+        })
+    }
+}
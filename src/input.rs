@@ -0,0 +1,428 @@
+// src/input.rs
+
+use crate::{scratch_tag, ADB};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputEvent {
+    pub timestamp: Duration,
+    pub device: String,
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputRecording {
+    pub events: Vec<InputEvent>,
+}
+
+impl InputEvent {
+    // Parses one line of `getevent -t` output, e.g.
+    // `[   74436.245706] /dev/input/event1: 0003 0039 00000000`.
+    fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix('[')?;
+        let (stamp, rest) = rest.split_once(']')?;
+        let (secs, micros) = stamp.trim().split_once('.')?;
+        let timestamp = Duration::from_secs(secs.parse().ok()?) + Duration::from_micros(micros.parse().ok()?);
+
+        let (device, fields) = rest.trim().split_once(':')?;
+        let mut fields = fields.split_whitespace();
+        let event_type = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let code = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let value = u32::from_str_radix(fields.next()?, 16).ok()? as i32;
+
+        Some(InputEvent {
+            timestamp,
+            device: device.trim().to_string(),
+            event_type,
+            code,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for InputEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>8}.{:06}] {}: {:04x} {:04x} {:08x}",
+            self.timestamp.as_secs(),
+            self.timestamp.subsec_micros(),
+            self.device,
+            self.event_type,
+            self.code,
+            self.value as u32
+        )
+    }
+}
+
+impl InputRecording {
+    pub fn parse(raw: &str) -> Self {
+        InputRecording {
+            events: raw.lines().filter_map(InputEvent::parse).collect(),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match (self.events.first(), self.events.last()) {
+            (Some(first), Some(last)) => last.timestamp.saturating_sub(first.timestamp),
+            _ => Duration::ZERO,
+        }
+    }
+
+    // Builds a single shell script so the relative timing between events is
+    // kept on the device instead of depending on adb round-trips. Long
+    // recordings easily outgrow the shell's argument limit, so the script is
+    // pushed as a file rather than passed on the command line.
+    fn to_replay_script(&self, speed: f64) -> String {
+        let mut script = Vec::with_capacity(self.events.len());
+        let mut previous = self.events.first().map(|event| event.timestamp);
+        for event in &self.events {
+            if let Some(prev) = previous {
+                let delay = event.timestamp.saturating_sub(prev).as_secs_f64() / speed;
+                if delay >= 0.001 {
+                    script.push(format!("sleep {:.3}", delay));
+                }
+            }
+            previous = Some(event.timestamp);
+            script.push(format!(
+                "sendevent {} {} {} {}",
+                event.device, event.event_type, event.code, event.value
+            ));
+        }
+        script.push(String::new());
+        script.join("\n")
+    }
+}
+
+// Local and remote copies of a replay script; the local one is removed on
+// drop, the remote one by the replay itself.
+struct ReplayScriptFile {
+    local: PathBuf,
+    remote: String,
+}
+
+impl ReplayScriptFile {
+    fn create(device: &str, script: &str) -> Result<Self, io::Error> {
+        let tag = scratch_tag(device);
+        let file = ReplayScriptFile {
+            local: std::env::temp_dir().join(format!("rust-adb-replay-{}.sh", tag)),
+            remote: format!("/data/local/tmp/rust-adb-replay-{}.sh", tag),
+        };
+        fs::write(&file.local, script)?;
+        Ok(file)
+    }
+}
+
+impl Drop for ReplayScriptFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.local);
+    }
+}
+
+impl fmt::Display for InputRecording {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalAction {
+    Back,
+    Home,
+    Recents,
+    Notifications,
+    QuickSettings,
+    CollapsePanels,
+    Screenshot,
+}
+
+impl GlobalAction {
+    fn to_command(self) -> &'static str {
+        match self {
+            GlobalAction::Back => "input keyevent KEYCODE_BACK",
+            GlobalAction::Home => "input keyevent KEYCODE_HOME",
+            GlobalAction::Recents => "input keyevent KEYCODE_APP_SWITCH",
+            GlobalAction::Notifications => "cmd statusbar expand-notifications",
+            GlobalAction::QuickSettings => "cmd statusbar expand-settings",
+            GlobalAction::CollapsePanels => "cmd statusbar collapse",
+            GlobalAction::Screenshot => "input keyevent KEYCODE_SYSRQ",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SwipeSpeed {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl SwipeSpeed {
+    fn duration(self) -> Duration {
+        match self {
+            SwipeSpeed::Slow => Duration::from_millis(800),
+            SwipeSpeed::Normal => Duration::from_millis(300),
+            SwipeSpeed::Fast => Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+type PctSwipe = ((f64, f64), (f64, f64));
+
+// Swipes are centered on the screen so the same distance works in every
+// direction without running off an edge.
+fn directional_swipe(direction: SwipeDirection, distance_pct: f64) -> Result<PctSwipe, io::Error> {
+    if !(0.0..=1.0).contains(&distance_pct) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Swipe distance must be within 0.0..=1.0, got {}", distance_pct),
+        ));
+    }
+    let near = 0.5 - distance_pct / 2.0;
+    let far = 0.5 + distance_pct / 2.0;
+    Ok(match direction {
+        SwipeDirection::Up => ((0.5, far), (0.5, near)),
+        SwipeDirection::Down => ((0.5, near), (0.5, far)),
+        SwipeDirection::Left => ((far, 0.5), (near, 0.5)),
+        SwipeDirection::Right => ((near, 0.5), (far, 0.5)),
+    })
+}
+
+const SHADE_SWIPE: PctSwipe = ((0.5, 0.0), (0.5, 0.6));
+
+fn pct_to_px(pct: f64, extent: u32) -> Result<u32, io::Error> {
+    if !(0.0..=1.0).contains(&pct) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Relative coordinate must be within 0.0..=1.0, got {}", pct),
+        ));
+    }
+    Ok((pct * extent.saturating_sub(1) as f64).round() as u32)
+}
+
+fn check_speed(speed: f64) -> Result<(), io::Error> {
+    if speed.is_finite() && speed > 0.0 {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Replay speed must be positive, got {}", speed),
+        ))
+    }
+}
+
+impl ADB {
+    pub fn record_input(&self, device: &str, duration: Duration) -> Result<InputRecording, io::Error> {
+        let raw = self.exec_shell_for(&format!("{} shell getevent -t", device), duration)?;
+        Ok(InputRecording::parse(&raw))
+    }
+
+    pub async fn record_input_async(&self, device: &str, duration: Duration) -> Result<InputRecording, io::Error> {
+        let raw = self
+            .exec_shell_for_async(&format!("{} shell getevent -t", device), duration)
+            .await?;
+        Ok(InputRecording::parse(&raw))
+    }
+
+    pub fn replay_input(&self, device: &str, recording: &InputRecording, speed: f64) -> Result<(), io::Error> {
+        check_speed(speed)?;
+        if recording.events.is_empty() {
+            return Ok(());
+        }
+        let script = ReplayScriptFile::create(device, &recording.to_replay_script(speed))?;
+        self.push(device, &script.local.to_string_lossy(), &script.remote)?;
+        let replayed = self.run_adb(&format!("{} shell sh {}", device, script.remote));
+        let _ = self.run_adb(&format!("{} shell rm -f {}", device, script.remote));
+        replayed.map(|_| ())
+    }
+
+    pub async fn replay_input_async(&self, device: &str, recording: &InputRecording, speed: f64) -> Result<(), io::Error> {
+        check_speed(speed)?;
+        if recording.events.is_empty() {
+            return Ok(());
+        }
+        let script = ReplayScriptFile::create(device, &recording.to_replay_script(speed))?;
+        self.push_async(device, &script.local.to_string_lossy(), &script.remote)
+            .await?;
+        // A replay lasts as long as the recording, so `ADB::timeout` does
+        // not apply to it.
+        let replayed = self
+            .run_adb_unbounded_async(&format!("{} shell sh {}", device, script.remote))
+            .await;
+        let _ = self
+            .run_adb_async(&format!("{} shell rm -f {}", device, script.remote))
+            .await;
+        replayed.map(|_| ())
+    }
+
+    pub fn tap(&self, device: &str, x: u32, y: u32) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell input tap {} {}", device, x, y))?;
+        Ok(())
+    }
+
+    pub async fn tap_async(&self, device: &str, x: u32, y: u32) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell input tap {} {}", device, x, y))
+            .await?;
+        Ok(())
+    }
+
+    pub fn swipe(&self, device: &str, from: (u32, u32), to: (u32, u32), duration: Duration) -> Result<(), io::Error> {
+        self.run_adb(&format!(
+            "{} shell input swipe {} {} {} {} {}",
+            device,
+            from.0,
+            from.1,
+            to.0,
+            to.1,
+            duration.as_millis()
+        ))?;
+        Ok(())
+    }
+
+    pub async fn swipe_async(&self, device: &str, from: (u32, u32), to: (u32, u32), duration: Duration) -> Result<(), io::Error> {
+        self.run_adb_async(&format!(
+            "{} shell input swipe {} {} {} {} {}",
+            device,
+            from.0,
+            from.1,
+            to.0,
+            to.1,
+            duration.as_millis()
+        ))
+        .await?;
+        Ok(())
+    }
+
+    pub fn tap_pct(&self, device: &str, x_pct: f64, y_pct: f64) -> Result<(), io::Error> {
+        let (width, height) = self.screen_size_cached(device)?;
+        self.tap(device, pct_to_px(x_pct, width)?, pct_to_px(y_pct, height)?)
+    }
+
+    pub async fn tap_pct_async(&self, device: &str, x_pct: f64, y_pct: f64) -> Result<(), io::Error> {
+        let (width, height) = self.screen_size_cached_async(device).await?;
+        self.tap_async(device, pct_to_px(x_pct, width)?, pct_to_px(y_pct, height)?)
+            .await
+    }
+
+    pub fn swipe_pct(&self, device: &str, from: (f64, f64), to: (f64, f64), duration: Duration) -> Result<(), io::Error> {
+        let (width, height) = self.screen_size_cached(device)?;
+        let from = (pct_to_px(from.0, width)?, pct_to_px(from.1, height)?);
+        let to = (pct_to_px(to.0, width)?, pct_to_px(to.1, height)?);
+        self.swipe(device, from, to, duration)
+    }
+
+    pub async fn swipe_pct_async(&self, device: &str, from: (f64, f64), to: (f64, f64), duration: Duration) -> Result<(), io::Error> {
+        let (width, height) = self.screen_size_cached_async(device).await?;
+        let from = (pct_to_px(from.0, width)?, pct_to_px(from.1, height)?);
+        let to = (pct_to_px(to.0, width)?, pct_to_px(to.1, height)?);
+        self.swipe_async(device, from, to, duration).await
+    }
+
+    pub fn send_keyevent(&self, device: &str, keycode: &str) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell input keyevent {}", device, keycode))?;
+        Ok(())
+    }
+
+    pub async fn send_keyevent_async(&self, device: &str, keycode: &str) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell input keyevent {}", device, keycode))
+            .await?;
+        Ok(())
+    }
+
+    pub fn perform_global_action(&self, device: &str, action: GlobalAction) -> Result<(), io::Error> {
+        self.run_adb(&format!("{} shell {}", device, action.to_command()))?;
+        Ok(())
+    }
+
+    pub async fn perform_global_action_async(&self, device: &str, action: GlobalAction) -> Result<(), io::Error> {
+        self.run_adb_async(&format!("{} shell {}", device, action.to_command()))
+            .await?;
+        Ok(())
+    }
+
+    fn swipe_direction(&self, device: &str, direction: SwipeDirection, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        let (from, to) = directional_swipe(direction, distance_pct)?;
+        self.swipe_pct(device, from, to, speed.duration())
+    }
+
+    async fn swipe_direction_async(&self, device: &str, direction: SwipeDirection, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        let (from, to) = directional_swipe(direction, distance_pct)?;
+        self.swipe_pct_async(device, from, to, speed.duration()).await
+    }
+
+    pub fn swipe_up(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction(device, SwipeDirection::Up, distance_pct, speed)
+    }
+
+    pub async fn swipe_up_async(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction_async(device, SwipeDirection::Up, distance_pct, speed)
+            .await
+    }
+
+    pub fn swipe_down(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction(device, SwipeDirection::Down, distance_pct, speed)
+    }
+
+    pub async fn swipe_down_async(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction_async(device, SwipeDirection::Down, distance_pct, speed)
+            .await
+    }
+
+    pub fn swipe_left(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction(device, SwipeDirection::Left, distance_pct, speed)
+    }
+
+    pub async fn swipe_left_async(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction_async(device, SwipeDirection::Left, distance_pct, speed)
+            .await
+    }
+
+    pub fn swipe_right(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction(device, SwipeDirection::Right, distance_pct, speed)
+    }
+
+    pub async fn swipe_right_async(&self, device: &str, distance_pct: f64, speed: SwipeSpeed) -> Result<(), io::Error> {
+        self.swipe_direction_async(device, SwipeDirection::Right, distance_pct, speed)
+            .await
+    }
+
+    pub fn open_notification_shade(&self, device: &str) -> Result<(), io::Error> {
+        let (from, to) = SHADE_SWIPE;
+        self.swipe_pct(device, from, to, SwipeSpeed::Normal.duration())
+    }
+
+    pub async fn open_notification_shade_async(&self, device: &str) -> Result<(), io::Error> {
+        let (from, to) = SHADE_SWIPE;
+        self.swipe_pct_async(device, from, to, SwipeSpeed::Normal.duration())
+            .await
+    }
+
+    // A second swipe from the top expands the shade into quick settings.
+    pub fn open_quick_settings(&self, device: &str) -> Result<(), io::Error> {
+        self.open_notification_shade(device)?;
+        self.open_notification_shade(device)
+    }
+
+    pub async fn open_quick_settings_async(&self, device: &str) -> Result<(), io::Error> {
+        self.open_notification_shade_async(device).await?;
+        self.open_notification_shade_async(device).await
+    }
+}
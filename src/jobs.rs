@@ -0,0 +1,135 @@
+// src/jobs.rs
+
+use crate::dumpsys::parse_duration;
+use crate::ADB;
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JobInfo {
+    pub uid: String,
+    pub job_id: i32,
+    pub package: String,
+    pub component: String,
+    pub periodic_interval: Option<Duration>,
+    pub required_constraints: Vec<String>,
+    pub unsatisfied_constraints: Vec<String>,
+    pub ready: Option<bool>,
+}
+
+fn constraint_list(value: &str) -> Vec<String> {
+    value
+        .split_whitespace()
+        .filter(|token| !token.starts_with('['))
+        .map(str::to_string)
+        .collect()
+}
+
+// Parses the `Registered N jobs:` section of `dumpsys jobscheduler`:
+//   JOB #u0a123/42: 1a2b3c com.foo/.SyncService
+//     PERIODIC: interval=+15m0s0ms flex=+5m0s0ms
+//     Required constraints: TIMING_DELAY CONNECTIVITY [0x90000000]
+//     Unsatisfied constraints: CONNECTIVITY [0x10000000]
+//     Ready: false (job=false user=true ...)
+fn parse_jobs(output: &str) -> Vec<JobInfo> {
+    let mut jobs: Vec<JobInfo> = Vec::new();
+    let mut in_registered = false;
+    let mut current: Option<JobInfo> = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Registered ") && trimmed.ends_with("jobs:") {
+            in_registered = true;
+            continue;
+        }
+        if !in_registered {
+            continue;
+        }
+        if !line.starts_with(' ') && !trimmed.is_empty() {
+            break;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("JOB #") {
+            jobs.extend(current.take());
+            let Some((key, rest)) = rest.split_once(':') else {
+                continue;
+            };
+            let Some((uid, job_id)) = key.split_once('/') else {
+                continue;
+            };
+            let component = rest.split_whitespace().last().unwrap_or_default().to_string();
+            current = Some(JobInfo {
+                uid: uid.to_string(),
+                job_id: job_id.parse().unwrap_or_default(),
+                package: component.split('/').next().unwrap_or_default().to_string(),
+                component,
+                ..JobInfo::default()
+            });
+            continue;
+        }
+
+        let Some(job) = current.as_mut() else {
+            continue;
+        };
+        if let Some(rest) = trimmed.strip_prefix("PERIODIC:") {
+            job.periodic_interval = rest
+                .split_whitespace()
+                .find_map(|token| token.strip_prefix("interval="))
+                .and_then(parse_duration);
+        } else if let Some(rest) = trimmed.strip_prefix("Required constraints:") {
+            job.required_constraints = constraint_list(rest);
+        } else if let Some(rest) = trimmed.strip_prefix("Unsatisfied constraints:") {
+            job.unsatisfied_constraints = constraint_list(rest);
+        } else if let Some(rest) = trimmed.strip_prefix("Ready:") {
+            job.ready = rest.split_whitespace().next().map(|ready| ready == "true");
+        }
+    }
+    jobs.extend(current);
+    jobs
+}
+
+fn check_run_output(output: &str, package: &str, job_id: i32) -> Result<(), io::Error> {
+    if output.contains("Running job") {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "Job {} of {} was not run: {}",
+            job_id,
+            package,
+            output.trim()
+        )))
+    }
+}
+
+impl ADB {
+    pub fn list_jobs(&self, device: &str, package: &str) -> Result<Vec<JobInfo>, io::Error> {
+        let output = self.run_adb(&format!("{} shell dumpsys jobscheduler {}", device, package))?;
+        Ok(parse_jobs(&output)
+            .into_iter()
+            .filter(|job| job.package == package)
+            .collect())
+    }
+
+    pub async fn list_jobs_async(&self, device: &str, package: &str) -> Result<Vec<JobInfo>, io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell dumpsys jobscheduler {}", device, package))
+            .await?;
+        Ok(parse_jobs(&output)
+            .into_iter()
+            .filter(|job| job.package == package)
+            .collect())
+    }
+
+    // `-f` runs the job even when its constraints aren't satisfied.
+    pub fn force_run_job(&self, device: &str, package: &str, job_id: i32) -> Result<(), io::Error> {
+        let output = self.run_adb(&format!("{} shell cmd jobscheduler run -f {} {}", device, package, job_id))?;
+        check_run_output(&output, package, job_id)
+    }
+
+    pub async fn force_run_job_async(&self, device: &str, package: &str, job_id: i32) -> Result<(), io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell cmd jobscheduler run -f {} {}", device, package, job_id))
+            .await?;
+        check_run_output(&output, package, job_id)
+    }
+}
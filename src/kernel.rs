@@ -0,0 +1,102 @@
+// src/kernel.rs
+
+use crate::ADB;
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KernelLogLevel {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 7,
+}
+
+impl KernelLogLevel {
+    fn from_priority(priority: u32) -> Self {
+        // The low three bits are the level; the rest is the syslog facility.
+        match priority & 7 {
+            0 => KernelLogLevel::Emergency,
+            1 => KernelLogLevel::Alert,
+            2 => KernelLogLevel::Critical,
+            3 => KernelLogLevel::Error,
+            4 => KernelLogLevel::Warning,
+            5 => KernelLogLevel::Notice,
+            6 => KernelLogLevel::Info,
+            _ => KernelLogLevel::Debug,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KmsgOptions {
+    // Only entries logged at or after this time since boot.
+    pub since: Option<Duration>,
+    // Only entries at this level or more severe.
+    pub level: Option<KernelLogLevel>,
+    // Clear the kernel ring buffer after reading; needs root.
+    pub clear: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelLogEntry {
+    pub timestamp: Duration,
+    pub level: KernelLogLevel,
+    pub message: String,
+}
+
+// Parses `dmesg -r` lines: `<6>[   12.345678] message`.
+fn parse_entry(line: &str) -> Option<KernelLogEntry> {
+    let rest = line.strip_prefix('<')?;
+    let (priority, rest) = rest.split_once('>')?;
+    let rest = rest.trim_start().strip_prefix('[')?;
+    let (stamp, message) = rest.split_once(']')?;
+    Some(KernelLogEntry {
+        timestamp: Duration::from_secs_f64(stamp.trim().parse().ok()?),
+        level: KernelLogLevel::from_priority(priority.parse().ok()?),
+        message: message.trim_start().to_string(),
+    })
+}
+
+fn parse_kernel_log(output: &str, options: &KmsgOptions) -> Vec<KernelLogEntry> {
+    output
+        .lines()
+        .filter_map(parse_entry)
+        .filter(|entry| options.since.is_none_or(|since| entry.timestamp >= since))
+        .filter(|entry| options.level.is_none_or(|level| entry.level <= level))
+        .collect()
+}
+
+impl ADB {
+    // Most user builds restrict dmesg to root, so `su` is tried when the
+    // plain read fails.
+    pub fn get_kernel_log(&self, device: &str, options: &KmsgOptions) -> Result<Vec<KernelLogEntry>, io::Error> {
+        let output = match self.run_adb(&format!("{} shell dmesg -r", device)) {
+            Ok(output) if !output.trim().is_empty() => output,
+            _ => self.run_adb(&format!("{} shell su -c \"dmesg -r\"", device))?,
+        };
+        if options.clear {
+            self.run_adb(&format!("{} shell su -c \"dmesg -C\"", device))?;
+        }
+        Ok(parse_kernel_log(&output, options))
+    }
+
+    pub async fn get_kernel_log_async(&self, device: &str, options: &KmsgOptions) -> Result<Vec<KernelLogEntry>, io::Error> {
+        let output = match self.run_adb_async(&format!("{} shell dmesg -r", device)).await {
+            Ok(output) if !output.trim().is_empty() => output,
+            _ => {
+                self.run_adb_async(&format!("{} shell su -c \"dmesg -r\"", device))
+                    .await?
+            }
+        };
+        if options.clear {
+            self.run_adb_async(&format!("{} shell su -c \"dmesg -C\"", device))
+                .await?;
+        }
+        Ok(parse_kernel_log(&output, options))
+    }
+}
@@ -1,14 +1,118 @@
 // src/lib.rs
 
-use std::process::{Command, Output};
-use std::io;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
 use tokio::process::Command as AsyncCommand;
 use tokio::time::timeout;
 
+mod activity;
+mod alarms;
+mod audio;
+mod automation;
+mod battery;
+mod diagnostics;
+mod display;
+mod dumpsys;
+mod emulator;
+mod forward;
+mod hardware;
+mod ime;
+mod input;
+mod jobs;
+mod kernel;
+mod location;
+mod memory;
+#[cfg(feature = "mirror")]
+mod mirror;
+mod monkey;
+mod netstats;
+mod network;
+mod pool;
+mod power;
+mod process;
+mod scheduler;
+mod screen;
+mod screenshot;
+mod storage;
+mod system;
+mod tcpdump;
+mod telephony;
+mod validation;
+mod wireless;
+
+pub use activity::ActivityInfo;
+pub use alarms::AlarmInfo;
+pub use audio::{AudioInfo, AudioStream, RingerMode, StreamVolume};
+pub use automation::{
+    BatchResult, CommandResult, LoopItems, StepAction, StepCondition, StepContext, StepHandler, StepResult, StepStatus,
+    Workflow, WorkflowEngine, WorkflowHandle, WorkflowObserver, WorkflowResult, WorkflowStep,
+};
+pub use battery::{BatteryHealth, BatteryInfo, BatteryStatus};
+pub use diagnostics::{ConnectivityReport, HttpProbe, LinkQuality, NetworkStatus, PingStats, Transport};
+pub use display::{DisplayInfo, DisplayMetrics, HdrType, Insets};
+pub use emulator::{
+    emulator_console_port, EmulatorConsole, EmulatorHandle, NetworkDelay, NetworkSpeed, RedirProtocol, Redirect,
+};
+pub use forward::{ForwardRule, ForwardTarget, ReverseRule};
+pub use hardware::{CpuCore, CpuInfo, GpuInfo};
+pub use ime::InputMethodGuard;
+pub use input::{GlobalAction, InputEvent, InputRecording, SwipeSpeed};
+pub use jobs::JobInfo;
+pub use kernel::{KernelLogEntry, KernelLogLevel, KmsgOptions};
+pub use location::{GpxPoint, GpxRoute};
+pub use memory::{MemoryReport, ProcessMemory};
+#[cfg(feature = "mirror")]
+pub use mirror::{DecodedFrame, FrameDecoder, MirrorOptions};
+pub use monkey::{MonkeyOptions, MonkeyResult};
+pub use netstats::UidDataUsage;
+pub use network::{PrivateDnsMode, WifiSecurity, WifiStatus};
+pub use pool::{DevicePool, FleetBatchResult, PoolResult, ShardingStrategy};
+pub use power::WakelockInfo;
+pub use process::ProcessSample;
+pub use scheduler::{CronSchedule, DeviceFilter, DeviceMatcher, Schedule, Scheduler, SchedulerEvent, SchedulerHandle};
+pub use screen::{
+    KeepRecording, ScopedRecordingOptions, ScopedVideo, ScreenRecordHandle, ScreenRecordOptions,
+    ScreenRecordProgress, ScreenRecordProgressCallback, ScreenStream,
+};
+pub use screenshot::{compare_screenshots, ComparisonOptions, DiffResult, Rect, ScreenshotOptions};
+pub use storage::{StorageVolume, VolumeKind};
+pub use system::{ApiLevelTooLow, BootInfo, BootReasonEntry, SystemInfo};
+pub use tcpdump::TcpdumpOptions;
+pub use telephony::{DataState, TelephonyInfo};
+pub use validation::{validate_workflow, ValidationIssue, ValidationReport};
+pub use wireless::{
+    MdnsService, MdnsServiceKind, WirelessDebugOptions, WirelessEvent, WirelessKeeper, WirelessKeeperOptions,
+    WirelessPairing,
+};
+
+// Quotes a value for the device shell, so it reaches the command as one
+// literal argument whatever characters it holds.
+pub(crate) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// Tag for scratch files named after a device. The process id and a counter
+// keep concurrent runs, in this process or another, from sharing a file.
+pub(crate) fn scratch_tag(device: &str) -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let serial: String = device.chars().filter(char::is_ascii_alphanumeric).collect();
+    format!("{}-{}-{}", serial, std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed))
+}
+
+#[derive(Clone)]
 pub struct ADB {
     bin: String,
     timeout: Duration,
+    screen_sizes: Arc<Mutex<HashMap<String, (u32, u32)>>>,
+    // Ring volume from before `set_ringer_mode` stepped it down to vibrate
+    // or silent, restored once it steps back up to normal.
+    ring_volumes: Arc<Mutex<HashMap<String, u32>>>,
 }
 
 impl ADB {
@@ -22,7 +126,12 @@ impl ADB {
             "macos" => format!("{}/{}", bin_path, Self::BIN_DARWIN),
             _ => format!("{}/{}", bin_path, Self::BIN_LINUX),
         };
-        ADB { bin, timeout }
+        ADB {
+            bin,
+            timeout,
+            screen_sizes: Arc::new(Mutex::new(HashMap::new())),
+            ring_volumes: Arc::new(Mutex::new(HashMap::new())),
+        }
     }
 
     fn exec_shell(&self, command: &str) -> Result<Output, io::Error> {
@@ -32,19 +141,120 @@ impl ADB {
     async fn exec_shell_async(&self, command: &str) -> Result<Output, io::Error> {
         let child = AsyncCommand::new(&self.bin)
             .arg(command)
+            .kill_on_drop(true)
             .output();
         timeout(self.timeout, child).await?
     }
 
+    // Like `exec_shell_async`, but without `self.timeout`, for long-running
+    // commands whose caller sets its own limit. Dropping the future still
+    // kills the command.
+    async fn exec_shell_unbounded_async(&self, command: &str) -> Result<Output, io::Error> {
+        AsyncCommand::new(&self.bin)
+            .arg(command)
+            .kill_on_drop(true)
+            .output()
+            .await
+    }
+
+    // Like `exec_shell`, but kills the command once `deadline` passes.
+    fn exec_shell_until(&self, command: &str, deadline: Instant) -> Result<Output, io::Error> {
+        let mut child = Command::new(&self.bin)
+            .arg(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut stdout = child.stdout.take().ok_or_else(|| io::Error::other("Failed to capture stdout"))?;
+        let mut stderr = child.stderr.take().ok_or_else(|| io::Error::other("Failed to capture stderr"))?;
+        let stdout_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+        let stderr_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stderr.read_to_end(&mut buf).map(|_| buf)
+        });
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                child.wait()?;
+                return Err(io::Error::new(io::ErrorKind::TimedOut, format!("Command timed out: {}", command)));
+            }
+            thread::sleep(Duration::from_millis(20));
+        };
+        let join = |reader: thread::JoinHandle<io::Result<Vec<u8>>>| {
+            reader
+                .join()
+                .map_err(|_| io::Error::other("Output reader thread panicked"))?
+        };
+        Ok(Output {
+            status,
+            stdout: join(stdout_reader)?,
+            stderr: join(stderr_reader)?,
+        })
+    }
+
+    fn spawn_shell(&self, command: &str) -> Result<Child, io::Error> {
+        Command::new(&self.bin)
+            .arg(command)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    }
+
+    // Runs a streaming command (getevent, logcat, ...) for a fixed time and
+    // returns everything it printed before being stopped.
+    fn exec_shell_for(&self, command: &str, duration: Duration) -> Result<String, io::Error> {
+        let mut child = Command::new(&self.bin)
+            .arg(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut stdout = child.stdout.take().ok_or_else(|| io::Error::other("Failed to capture stdout"))?;
+        let reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).map(|_| buf)
+        });
+        thread::sleep(duration);
+        let _ = child.kill();
+        child.wait()?;
+        let buf = reader
+            .join()
+            .map_err(|_| io::Error::other("Output reader thread panicked"))??;
+        Ok(String::from_utf8_lossy(&buf).to_string())
+    }
+
+    async fn exec_shell_for_async(&self, command: &str, duration: Duration) -> Result<String, io::Error> {
+        let mut child = AsyncCommand::new(&self.bin)
+            .arg(command)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let mut stdout = child.stdout.take().ok_or_else(|| io::Error::other("Failed to capture stdout"))?;
+        let reader = tokio::spawn(async move {
+            let mut buf = Vec::new();
+            stdout.read_to_end(&mut buf).await.map(|_| buf)
+        });
+        tokio::time::sleep(duration).await;
+        let _ = child.start_kill();
+        child.wait().await?;
+        let buf = reader.await.map_err(io::Error::other)??;
+        Ok(String::from_utf8_lossy(&buf).to_string())
+    }
+
     pub fn run_adb(&self, command: &str) -> Result<String, io::Error> {
         let output = self.exec_shell(command)?;
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).to_string())
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Command failed: {}", String::from_utf8_lossy(&output.stderr)),
-            ))
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
         }
     }
 
@@ -53,10 +263,59 @@ impl ADB {
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).to_string())
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Command failed: {}", String::from_utf8_lossy(&output.stderr)),
-            ))
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+
+    fn run_adb_until(&self, command: &str, deadline: Instant) -> Result<String, io::Error> {
+        let output = self.exec_shell_until(command, deadline)?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+
+    async fn run_adb_unbounded_async(&self, command: &str) -> Result<String, io::Error> {
+        let output = self.exec_shell_unbounded_async(command).await?;
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        } else {
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+
+    // Like `run_adb`, but keeps stdout as raw bytes for binary output.
+    fn run_adb_bytes(&self, command: &str) -> Result<Vec<u8>, io::Error> {
+        let output = self.exec_shell(command)?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+
+    async fn run_adb_bytes_async(&self, command: &str) -> Result<Vec<u8>, io::Error> {
+        let output = self.exec_shell_async(command).await?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
         }
     }
 
@@ -80,6 +339,16 @@ impl ADB {
         Ok(devices)
     }
 
+    pub fn get_prop(&self, device: &str, name: &str) -> Result<String, io::Error> {
+        let output = self.run_adb(&format!("{} shell getprop {}", device, name))?;
+        Ok(output.trim().to_string())
+    }
+
+    pub async fn get_prop_async(&self, device: &str, name: &str) -> Result<String, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell getprop {}", device, name)).await?;
+        Ok(output.trim().to_string())
+    }
+
     pub fn start_server(&self) -> Result<(), io::Error> {
         self.run_adb("start-server")?;
         Ok(())
@@ -117,13 +386,11 @@ impl ADB {
     }
 
     pub fn get_screenshot_png(&self, device: &str) -> Result<Vec<u8>, io::Error> {
-        let output = self.run_adb(&format!("{} exec-out screencap -p", device))?;
-        Ok(output.into_bytes())
+        self.run_adb_bytes(&format!("{} exec-out screencap -p", device))
     }
 
     pub async fn get_screenshot_png_async(&self, device: &str) -> Result<Vec<u8>, io::Error> {
-        let output = self.run_adb_async(&format!("{} exec-out screencap -p", device)).await?;
-        Ok(output.into_bytes())
+        self.run_adb_bytes_async(&format!("{} exec-out screencap -p", device)).await
     }
 
     pub fn install_app(&self, device: &str, apk_path: &str) -> Result<String, io::Error> {
@@ -142,6 +409,22 @@ impl ADB {
         self.run_adb_async(&format!("{} uninstall {}", device, package_name)).await
     }
 
+    pub fn push(&self, device: &str, local_path: &str, remote_path: &str) -> Result<String, io::Error> {
+        self.run_adb(&format!("{} push {} {}", device, local_path, remote_path))
+    }
+
+    pub async fn push_async(&self, device: &str, local_path: &str, remote_path: &str) -> Result<String, io::Error> {
+        self.run_adb_async(&format!("{} push {} {}", device, local_path, remote_path)).await
+    }
+
+    pub fn pull(&self, device: &str, remote_path: &str, local_path: &str) -> Result<String, io::Error> {
+        self.run_adb(&format!("{} pull {} {}", device, remote_path, local_path))
+    }
+
+    pub async fn pull_async(&self, device: &str, remote_path: &str, local_path: &str) -> Result<String, io::Error> {
+        self.run_adb_async(&format!("{} pull {} {}", device, remote_path, local_path)).await
+    }
+
     pub fn logcat(&self, device: &str) -> Result<String, io::Error> {
         self.run_adb(&format!("{} logcat", device))
     }
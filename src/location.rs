@@ -0,0 +1,347 @@
+// src/location.rs
+
+use crate::emulator::{emulator_console_port, with_console_async, EmulatorConsole};
+use crate::ADB;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+// Test providers are driven through `cmd location`, which gained them in
+// Android 12.
+const TEST_PROVIDER_API: u32 = 31;
+const TEST_PROVIDER: &str = "gps";
+// Used between route points without timestamps.
+const DEFAULT_POINT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpxPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    // Meters, from `<ele>`.
+    pub altitude: Option<f64>,
+    // Seconds since the Unix epoch, from `<time>`.
+    pub time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpxRoute {
+    pub points: Vec<GpxPoint>,
+}
+
+impl GpxRoute {
+    // Takes the track points of a GPX file, or its route points when it has
+    // no track, falling back to its waypoints.
+    pub fn parse(text: &str) -> Result<Self, io::Error> {
+        for tag in ["trkpt", "rtept", "wpt"] {
+            let points = parse_points(text, tag)?;
+            if !points.is_empty() {
+                return Ok(GpxRoute { points });
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::InvalidData, "GPX file has no track, route or waypoints"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // How long to wait before moving to point `index`. Recorded timestamps
+    // are replayed `speed` times faster than real time.
+    fn delay(&self, index: usize, speed: f64) -> Duration {
+        let speed = if speed > 0.0 { speed } else { 1.0 };
+        let previous = self.points.get(index.wrapping_sub(1)).and_then(|point| point.time);
+        match (previous, self.points.get(index).and_then(|point| point.time)) {
+            (Some(previous), Some(time)) => Duration::from_secs_f64((time - previous).max(0.0) / speed),
+            _ => DEFAULT_POINT_INTERVAL.div_f64(speed),
+        }
+    }
+}
+
+fn parse_points(text: &str, tag: &str) -> Result<Vec<GpxPoint>, io::Error> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut points = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        // Skip longer tag names that share the prefix.
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            continue;
+        }
+        let header_end = rest
+            .find('>')
+            .ok_or_else(|| gpx_error(&format!("unterminated <{}> element", tag)))?;
+        let header = &rest[..header_end];
+        let body = if header.ends_with('/') {
+            ""
+        } else {
+            let end = rest
+                .find(&close)
+                .ok_or_else(|| gpx_error(&format!("missing </{}>", tag)))?;
+            &rest[header_end + 1..end]
+        };
+        rest = &rest[header_end..];
+
+        let coordinate = |name: &str| {
+            attribute(header, name)
+                .and_then(|value| value.parse::<f64>().ok())
+                .ok_or_else(|| gpx_error(&format!("<{}> has no valid {} attribute", tag, name)))
+        };
+        points.push(GpxPoint {
+            latitude: coordinate("lat")?,
+            longitude: coordinate("lon")?,
+            altitude: element(body, "ele").and_then(|value| value.parse().ok()),
+            time: element(body, "time").and_then(parse_timestamp),
+        });
+    }
+    Ok(points)
+}
+
+fn gpx_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid GPX: {}", message))
+}
+
+fn attribute<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.split_whitespace().find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| value.trim_end_matches('/').trim_matches(|c| c == '"' || c == '\''))
+    })
+}
+
+fn element<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let start = body.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + body[start..].find(&format!("</{}>", name))?;
+    Some(body[start..end].trim())
+}
+
+// ISO 8601 as written by GPS loggers, e.g. `2024-05-01T12:30:00.5Z` or
+// `2024-05-01T14:30:00+02:00`.
+fn parse_timestamp(text: &str) -> Option<f64> {
+    let (date, time) = text.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(index) => (&time[..index], &time[index..]),
+        None => (time, ""),
+    };
+    let mut clock = time.splitn(3, ':');
+    let hours: f64 = clock.next()?.parse().ok()?;
+    let minutes: f64 = clock.next()?.parse().ok()?;
+    let seconds: f64 = clock.next().unwrap_or("0").parse().ok()?;
+
+    let offset = match offset.split_at_checked(1) {
+        Some((sign @ ("+" | "-"), zone)) => {
+            let (zone_hours, zone_minutes) = zone.split_once(':').unwrap_or((zone, "0"));
+            let minutes = zone_hours.parse::<f64>().ok()? * 60.0 + zone_minutes.parse::<f64>().ok()?;
+            if sign == "-" {
+                -minutes * 60.0
+            } else {
+                minutes * 60.0
+            }
+        }
+        _ => 0.0,
+    };
+
+    let days = days_from_civil(year, month, day) as f64;
+    Some(days * 86_400.0 + hours * 3_600.0 + minutes * 60.0 + seconds - offset)
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+// Where fixes for a device go: the console of an emulator, or a `gps` test
+// provider on a physical device.
+enum MockTarget {
+    Console(u16),
+    TestProvider,
+}
+
+fn no_test_provider(error: io::Error) -> io::Error {
+    if error.kind() != io::ErrorKind::Unsupported {
+        return error;
+    }
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{}; select a mock location app with set_mock_location_app instead", error),
+    )
+}
+
+fn test_provider_location(device: &str, latitude: f64, longitude: f64) -> String {
+    format!(
+        "{} shell cmd location providers set-test-provider-location {} --location {},{}",
+        device, TEST_PROVIDER, latitude, longitude
+    )
+}
+
+impl ADB {
+    fn mock_target(&self, device: &str) -> Result<MockTarget, io::Error> {
+        if let Some(port) = emulator_console_port(device) {
+            return Ok(MockTarget::Console(port));
+        }
+        self.require_api(device, TEST_PROVIDER_API).map_err(no_test_provider)?;
+        self.run_adb(&format!("{} shell appops set com.android.shell android:mock_location allow", device))?;
+        // Fails when the provider is already there, which is fine.
+        let _ = self.run_adb(&format!("{} shell cmd location providers add-test-provider {}", device, TEST_PROVIDER));
+        self.run_adb(&format!(
+            "{} shell cmd location providers set-test-provider-enabled {} true",
+            device, TEST_PROVIDER
+        ))?;
+        Ok(MockTarget::TestProvider)
+    }
+
+    async fn mock_target_async(&self, device: &str) -> Result<MockTarget, io::Error> {
+        if let Some(port) = emulator_console_port(device) {
+            return Ok(MockTarget::Console(port));
+        }
+        self.require_api_async(device, TEST_PROVIDER_API)
+            .await
+            .map_err(no_test_provider)?;
+        self.run_adb_async(&format!("{} shell appops set com.android.shell android:mock_location allow", device))
+            .await?;
+        let _ = self
+            .run_adb_async(&format!("{} shell cmd location providers add-test-provider {}", device, TEST_PROVIDER))
+            .await;
+        self.run_adb_async(&format!(
+            "{} shell cmd location providers set-test-provider-enabled {} true",
+            device, TEST_PROVIDER
+        ))
+        .await?;
+        Ok(MockTarget::TestProvider)
+    }
+
+    fn apply_fix(&self, device: &str, target: &MockTarget, point: &GpxPoint) -> Result<(), io::Error> {
+        match target {
+            MockTarget::Console(port) => {
+                EmulatorConsole::connect(*port)?.geo_fix(point.latitude, point.longitude, point.altitude)
+            }
+            MockTarget::TestProvider => {
+                self.run_adb(&test_provider_location(device, point.latitude, point.longitude))?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn apply_fix_async(&self, device: &str, target: &MockTarget, point: &GpxPoint) -> Result<(), io::Error> {
+        match target {
+            MockTarget::Console(_) => {
+                let point = *point;
+                with_console_async(device, move |console| {
+                    console.geo_fix(point.latitude, point.longitude, point.altitude)
+                })
+                .await
+            }
+            MockTarget::TestProvider => {
+                self.run_adb_async(&test_provider_location(device, point.latitude, point.longitude))
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_emulator_geo_fix(&self, emulator: &str, latitude: f64, longitude: f64, altitude: Option<f64>) -> Result<(), io::Error> {
+        EmulatorConsole::for_device(emulator)?.geo_fix(latitude, longitude, altitude)
+    }
+
+    pub async fn set_emulator_geo_fix_async(
+        &self,
+        emulator: &str,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<f64>,
+    ) -> Result<(), io::Error> {
+        with_console_async(emulator, move |console| console.geo_fix(latitude, longitude, altitude)).await
+    }
+
+    // Grants the mock location app-op to `package`, installing it from `apk`
+    // first if given. Apps feeding their own locations are the only option on
+    // devices older than Android 12; the app then has to be told where to go.
+    pub fn set_mock_location_app(&self, device: &str, package: &str, apk: Option<&str>) -> Result<(), io::Error> {
+        if let Some(apk) = apk {
+            self.install_app(device, &format!("-r {}", apk))?;
+        }
+        self.run_adb(&format!("{} shell appops set {} android:mock_location allow", device, package))?;
+        Ok(())
+    }
+
+    pub async fn set_mock_location_app_async(&self, device: &str, package: &str, apk: Option<&str>) -> Result<(), io::Error> {
+        if let Some(apk) = apk {
+            self.install_app_async(device, &format!("-r {}", apk)).await?;
+        }
+        self.run_adb_async(&format!("{} shell appops set {} android:mock_location allow", device, package))
+            .await?;
+        Ok(())
+    }
+
+    // Moves the device to the given position: through the console on
+    // emulators, and through a `gps` test provider on Android 12 and later.
+    // The altitude only reaches emulators.
+    pub fn set_mock_location(&self, device: &str, latitude: f64, longitude: f64, altitude: Option<f64>) -> Result<(), io::Error> {
+        let target = self.mock_target(device)?;
+        self.apply_fix(device, &target, &GpxPoint { latitude, longitude, altitude, time: None })
+    }
+
+    pub async fn set_mock_location_async(
+        &self,
+        device: &str,
+        latitude: f64,
+        longitude: f64,
+        altitude: Option<f64>,
+    ) -> Result<(), io::Error> {
+        let target = self.mock_target_async(device).await?;
+        self.apply_fix_async(device, &target, &GpxPoint { latitude, longitude, altitude, time: None })
+            .await
+    }
+
+    // Removes the test provider, handing location back to the real one.
+    // Emulators keep their last fix.
+    pub fn clear_mock_location(&self, device: &str) -> Result<(), io::Error> {
+        if emulator_console_port(device).is_some() {
+            return Ok(());
+        }
+        self.run_adb(&format!("{} shell cmd location providers remove-test-provider {}", device, TEST_PROVIDER))?;
+        Ok(())
+    }
+
+    pub async fn clear_mock_location_async(&self, device: &str) -> Result<(), io::Error> {
+        if emulator_console_port(device).is_some() {
+            return Ok(());
+        }
+        self.run_adb_async(&format!("{} shell cmd location providers remove-test-provider {}", device, TEST_PROVIDER))
+            .await?;
+        Ok(())
+    }
+
+    // Walks the device along the route, replaying the recorded timing
+    // `speed` times faster (2.0 plays at double pace). Points without
+    // timestamps are one second apart.
+    pub fn play_gpx_route(&self, device: &str, route: &GpxRoute, speed: f64) -> Result<(), io::Error> {
+        let target = self.mock_target(device)?;
+        for (index, point) in route.points.iter().enumerate() {
+            if index > 0 {
+                std::thread::sleep(route.delay(index, speed));
+            }
+            self.apply_fix(device, &target, point)?;
+        }
+        Ok(())
+    }
+
+    pub async fn play_gpx_route_async(&self, device: &str, route: &GpxRoute, speed: f64) -> Result<(), io::Error> {
+        let target = self.mock_target_async(device).await?;
+        for (index, point) in route.points.iter().enumerate() {
+            if index > 0 {
+                tokio::time::sleep(route.delay(index, speed)).await;
+            }
+            self.apply_fix_async(device, &target, point).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+// src/memory.rs
+
+use crate::ADB;
+use std::cmp::Reverse;
+use std::io;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessMemory {
+    pub name: String,
+    pub pid: u32,
+    pub pss_kb: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    // From /proc/meminfo.
+    pub total_kb: u64,
+    pub free_kb: u64,
+    pub available_kb: u64,
+    pub buffers_kb: u64,
+    pub cached_kb: u64,
+    pub swap_total_kb: u64,
+    pub swap_free_kb: u64,
+    // From the `dumpsys meminfo` summary.
+    pub total_ram_kb: Option<u64>,
+    pub free_ram_kb: Option<u64>,
+    pub used_ram_kb: Option<u64>,
+    pub lost_ram_kb: Option<u64>,
+    // Sorted by PSS, largest first.
+    pub processes: Vec<ProcessMemory>,
+}
+
+impl MemoryReport {
+    pub fn top_memory_consumers(&self, n: usize) -> &[ProcessMemory] {
+        &self.processes[..n.min(self.processes.len())]
+    }
+
+    pub fn used_kb(&self) -> u64 {
+        self.total_kb.saturating_sub(self.available_kb)
+    }
+
+    fn parse(proc_meminfo: &str, dumpsys: &str) -> Self {
+        let mut report = MemoryReport::default();
+
+        for line in proc_meminfo.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().trim_end_matches("kB").trim().parse().unwrap_or(0);
+            match key {
+                "MemTotal" => report.total_kb = value,
+                "MemFree" => report.free_kb = value,
+                "MemAvailable" => report.available_kb = value,
+                "Buffers" => report.buffers_kb = value,
+                "Cached" => report.cached_kb = value,
+                "SwapTotal" => report.swap_total_kb = value,
+                "SwapFree" => report.swap_free_kb = value,
+                _ => {}
+            }
+        }
+
+        let mut in_pss_section = false;
+        for line in dumpsys.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("Total PSS by process:") {
+                in_pss_section = true;
+                continue;
+            }
+            if in_pss_section {
+                match parse_process_line(trimmed) {
+                    Some(process) => report.processes.push(process),
+                    None => in_pss_section = false,
+                }
+                continue;
+            }
+
+            let Some((key, value)) = trimmed.split_once(':') else {
+                continue;
+            };
+            let value = parse_kb(value);
+            match key {
+                "Total RAM" => report.total_ram_kb = value,
+                "Free RAM" => report.free_ram_kb = value,
+                "Used RAM" => report.used_ram_kb = value,
+                "Lost RAM" => report.lost_ram_kb = value,
+                _ => {}
+            }
+        }
+
+        report.processes.sort_by_key(|process| Reverse(process.pss_kb));
+        report
+    }
+}
+
+// dumpsys prints sizes like `5,709,744K (status normal)`.
+fn parse_kb(value: &str) -> Option<u64> {
+    let number = value.trim().split('K').next()?;
+    number.replace(',', "").trim().parse().ok()
+}
+
+// `345,678K: com.android.systemui (pid 1234 / activities)`
+fn parse_process_line(line: &str) -> Option<ProcessMemory> {
+    let (size, rest) = line.split_once(':')?;
+    let pss_kb = parse_kb(size)?;
+    let (name, rest) = rest.trim().rsplit_once("(pid ")?;
+    let pid = rest
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()?;
+    Some(ProcessMemory {
+        name: name.trim().to_string(),
+        pid,
+        pss_kb,
+    })
+}
+
+impl ADB {
+    pub fn get_memory_report(&self, device: &str) -> Result<MemoryReport, io::Error> {
+        let proc_meminfo = self.run_adb(&format!("{} shell cat /proc/meminfo", device))?;
+        let dumpsys = self.run_adb(&format!("{} shell dumpsys meminfo", device))?;
+        Ok(MemoryReport::parse(&proc_meminfo, &dumpsys))
+    }
+
+    pub async fn get_memory_report_async(&self, device: &str) -> Result<MemoryReport, io::Error> {
+        let proc_meminfo = self
+            .run_adb_async(&format!("{} shell cat /proc/meminfo", device))
+            .await?;
+        let dumpsys = self
+            .run_adb_async(&format!("{} shell dumpsys meminfo", device))
+            .await?;
+        Ok(MemoryReport::parse(&proc_meminfo, &dumpsys))
+    }
+}
@@ -0,0 +1,71 @@
+// src/mirror.rs
+
+use crate::{ScreenRecordOptions, ADB};
+use futures::StreamExt;
+use std::io;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedFrame {
+    pub width: u32,
+    pub height: u32,
+    // Tightly packed RGBA8 pixels, `width * height * 4` bytes.
+    pub data: Vec<u8>,
+    pub timestamp: Duration,
+}
+
+// Turns chunks of the raw H.264 stream into frames. Chunks are not aligned to
+// NAL units, so implementations are expected to buffer partial data.
+pub trait FrameDecoder {
+    fn decode(&mut self, chunk: &[u8]) -> Result<Vec<DecodedFrame>, io::Error>;
+}
+
+#[derive(Debug, Clone)]
+pub struct MirrorOptions {
+    pub record: ScreenRecordOptions,
+    pub target_fps: u32,
+}
+
+impl Default for MirrorOptions {
+    fn default() -> Self {
+        MirrorOptions {
+            record: ScreenRecordOptions::default(),
+            target_fps: 30,
+        }
+    }
+}
+
+impl ADB {
+    // Every frame is decoded so the decoder keeps its reference frames, but
+    // the callback only sees frames spaced at least `1 / target_fps` apart.
+    // Mirroring ends when the callback breaks or the stream closes.
+    pub async fn mirror_screen<D, F>(
+        &self,
+        device: &str,
+        options: &MirrorOptions,
+        decoder: &mut D,
+        mut on_frame: F,
+    ) -> Result<(), io::Error>
+    where
+        D: FrameDecoder,
+        F: FnMut(DecodedFrame) -> ControlFlow<()>,
+    {
+        let interval = Duration::from_secs(1) / options.target_fps.max(1);
+        let mut last_delivered: Option<Instant> = None;
+        let mut stream = self.stream_screen_async(device, &options.record).await?;
+
+        while let Some(chunk) = stream.next().await {
+            for frame in decoder.decode(&chunk?)? {
+                if last_delivered.is_some_and(|last| last.elapsed() < interval) {
+                    continue;
+                }
+                last_delivered = Some(Instant::now());
+                if on_frame(frame).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
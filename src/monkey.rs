@@ -0,0 +1,136 @@
+// src/monkey.rs
+
+use crate::ADB;
+use std::io;
+use std::process::Output;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct MonkeyOptions {
+    pub package: Option<String>,
+    pub event_count: u32,
+    pub seed: Option<u64>,
+    pub throttle: Option<Duration>,
+    pub categories: Vec<String>,
+    pub ignore_crashes: bool,
+}
+
+impl Default for MonkeyOptions {
+    fn default() -> Self {
+        MonkeyOptions {
+            package: None,
+            event_count: 500,
+            seed: None,
+            throttle: None,
+            categories: Vec::new(),
+            ignore_crashes: false,
+        }
+    }
+}
+
+impl MonkeyOptions {
+    fn to_command(&self) -> String {
+        let mut args = vec!["monkey".to_string()];
+        if let Some(package) = &self.package {
+            args.push(format!("-p {}", package));
+        }
+        for category in &self.categories {
+            args.push(format!("-c {}", category));
+        }
+        if let Some(seed) = self.seed {
+            args.push(format!("-s {}", seed));
+        }
+        if let Some(throttle) = self.throttle {
+            args.push(format!("--throttle {}", throttle.as_millis()));
+        }
+        if self.ignore_crashes {
+            args.push("--ignore-crashes".to_string());
+        }
+        args.push("-v".to_string());
+        args.push(self.event_count.to_string());
+        args.join(" ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonkeyResult {
+    pub seed: Option<u64>,
+    pub events_injected: u32,
+    pub completed: bool,
+    pub crashes: Vec<String>,
+    pub anrs: Vec<String>,
+    pub output: String,
+}
+
+impl MonkeyResult {
+    pub fn is_clean(&self) -> bool {
+        self.completed && self.crashes.is_empty() && self.anrs.is_empty()
+    }
+
+    // Monkey exits with a non-zero status when it aborts on a crash, so a
+    // failed run still counts as a result once monkey itself has started.
+    // Without the `:Monkey:` header adb never got that far (no device,
+    // missing binary, ...) and the failure is reported as an error.
+    fn from_output(output: &Output) -> Result<Self, io::Error> {
+        let result = Self::parse(output);
+        let started = result.output.lines().any(|line| line.trim().starts_with(":Monkey:"));
+        if output.status.success() || started {
+            Ok(result)
+        } else {
+            Err(io::Error::other(format!(
+                "Command failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )))
+        }
+    }
+
+    fn parse(output: &Output) -> Self {
+        let text = format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let mut result = MonkeyResult {
+            seed: None,
+            events_injected: 0,
+            completed: false,
+            crashes: Vec::new(),
+            anrs: Vec::new(),
+            output: String::new(),
+        };
+
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix(":Monkey: seed=") {
+                result.seed = rest.split_whitespace().next().and_then(|seed| seed.parse().ok());
+            } else if let Some(rest) = line.strip_prefix("Events injected:") {
+                result.events_injected = rest.trim().parse().unwrap_or(0);
+            } else if line.starts_with("// Monkey finished") {
+                result.completed = true;
+            } else if let Some(rest) = line.strip_prefix("// CRASH:") {
+                result.crashes.push(rest.trim().to_string());
+            } else if let Some(rest) = line.strip_prefix("// NOT RESPONDING:") {
+                result.anrs.push(rest.trim().to_string());
+            }
+        }
+
+        result.output = text;
+        result
+    }
+}
+
+impl ADB {
+    pub fn run_monkey(&self, device: &str, options: &MonkeyOptions) -> Result<MonkeyResult, io::Error> {
+        let output = self.exec_shell(&format!("{} shell {}", device, options.to_command()))?;
+        MonkeyResult::from_output(&output)
+    }
+
+    // A run lasts as long as its events take, so like the sync version it is
+    // not cut off at `ADB::timeout`.
+    pub async fn run_monkey_async(&self, device: &str, options: &MonkeyOptions) -> Result<MonkeyResult, io::Error> {
+        let output = self
+            .exec_shell_unbounded_async(&format!("{} shell {}", device, options.to_command()))
+            .await?;
+        MonkeyResult::from_output(&output)
+    }
+}
@@ -0,0 +1,117 @@
+// src/netstats.rs
+
+use crate::dumpsys::parse_package_uids;
+use crate::ADB;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UidDataUsage {
+    pub uid: u32,
+    pub packages: Vec<String>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+}
+
+impl UidDataUsage {
+    pub fn total_bytes(&self) -> u64 {
+        self.rx_bytes + self.tx_bytes
+    }
+}
+
+// Walks the `UID stats:` section of `dumpsys netstats detail`:
+//   ident=[{type=WIFI, ...}] uid=10123 set=DEFAULT tag=0x0
+//     NetworkStatsHistory: bucketDuration=7200
+//       st=1690000000 rb=12345 rp=10 tb=2345 tp=5 op=0
+// Buckets are summed when they start at or after `since` (Unix seconds), so
+// the effective window is rounded to the bucket duration, usually 2 hours.
+fn parse_netstats(output: &str, since: u64, packages: &HashMap<u32, Vec<String>>) -> Vec<UidDataUsage> {
+    let mut usage: BTreeMap<u32, UidDataUsage> = BTreeMap::new();
+    let mut in_uid_stats = false;
+    let mut current_uid = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        // Section headers: `Dev stats:`, `Xt stats:`, `UID stats:`, `UID tag stats:`.
+        if trimmed.ends_with("stats:") {
+            in_uid_stats = trimmed == "UID stats:";
+            current_uid = None;
+            continue;
+        }
+        if !in_uid_stats {
+            continue;
+        }
+
+        if trimmed.starts_with("ident=") {
+            current_uid = trimmed
+                .split_whitespace()
+                .find_map(|token| token.strip_prefix("uid="))
+                .and_then(|uid| uid.parse().ok());
+        } else if let (Some(uid), true) = (current_uid, trimmed.starts_with("st=")) {
+            let mut start = 0;
+            let entry = usage.entry(uid).or_insert_with(|| UidDataUsage {
+                uid,
+                packages: packages.get(&uid).cloned().unwrap_or_default(),
+                ..UidDataUsage::default()
+            });
+            let mut bucket = [0u64; 4];
+            for token in trimmed.split_whitespace() {
+                let Some((key, value)) = token.split_once('=') else {
+                    continue;
+                };
+                let value = value.parse().unwrap_or(0);
+                match key {
+                    "st" => start = value,
+                    "rb" => bucket[0] = value,
+                    "rp" => bucket[1] = value,
+                    "tb" => bucket[2] = value,
+                    "tp" => bucket[3] = value,
+                    _ => {}
+                }
+            }
+            if start >= since {
+                entry.rx_bytes += bucket[0];
+                entry.rx_packets += bucket[1];
+                entry.tx_bytes += bucket[2];
+                entry.tx_packets += bucket[3];
+            }
+        }
+    }
+
+    usage.into_values().filter(|entry| entry.total_bytes() > 0).collect()
+}
+
+fn period_start(period: Duration) -> u64 {
+    SystemTime::now()
+        .checked_sub(period)
+        .and_then(|start| start.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |start| start.as_secs())
+}
+
+impl ADB {
+    pub fn get_data_usage(&self, device: &str, period: Duration) -> Result<Vec<UidDataUsage>, io::Error> {
+        let since = period_start(period);
+        let output = self.run_adb(&format!("{} shell dumpsys netstats detail", device))?;
+        let packages = self
+            .run_adb(&format!("{} shell pm list packages -U", device))
+            .map(|output| parse_package_uids(&output))
+            .unwrap_or_default();
+        Ok(parse_netstats(&output, since, &packages))
+    }
+
+    pub async fn get_data_usage_async(&self, device: &str, period: Duration) -> Result<Vec<UidDataUsage>, io::Error> {
+        let since = period_start(period);
+        let output = self
+            .run_adb_async(&format!("{} shell dumpsys netstats detail", device))
+            .await?;
+        let packages = self
+            .run_adb_async(&format!("{} shell pm list packages -U", device))
+            .await
+            .map(|output| parse_package_uids(&output))
+            .unwrap_or_default();
+        Ok(parse_netstats(&output, since, &packages))
+    }
+}
@@ -0,0 +1,328 @@
+// src/network.rs
+
+use crate::{shell_quote, DataState, ADB};
+use std::io;
+use std::time::{Duration, Instant};
+
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+const SETTLE_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WifiStatus {
+    pub enabled: bool,
+    pub connected: bool,
+    pub ssid: Option<String>,
+    pub bssid: Option<String>,
+    pub rssi: Option<i32>,
+    pub link_speed_mbps: Option<u32>,
+    pub frequency_mhz: Option<u32>,
+}
+
+impl WifiStatus {
+    // Both `cmd wifi status` and `dumpsys wifi` print a WifiInfo line:
+    // `SSID: "Home", BSSID: 02:00:00:00:00:00, ..., Supplicant state: COMPLETED,
+    //  RSSI: -55, Link speed: 433Mbps, ..., Frequency: 5180MHz, ...`
+    fn parse(output: &str) -> Self {
+        let mut status = WifiStatus {
+            enabled: output.contains("Wifi is enabled") || output.contains("Wi-Fi is enabled"),
+            ..WifiStatus::default()
+        };
+        let Some(info) = output
+            .lines()
+            .find(|line| line.contains("SSID: ") && line.contains("RSSI: "))
+        else {
+            return status;
+        };
+
+        let mut supplicant_completed = false;
+        for field in info.split(", ") {
+            let Some((key, value)) = field.split_once(": ") else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().trim_start_matches("mWifiInfo ") {
+                "SSID" => {
+                    status.ssid = Some(value.trim_matches('"').to_string())
+                        .filter(|ssid| !ssid.is_empty() && ssid != "<unknown ssid>")
+                }
+                "BSSID" => {
+                    status.bssid = Some(value.to_string()).filter(|bssid| bssid != "<none>" && bssid != "null")
+                }
+                "RSSI" => status.rssi = value.parse().ok(),
+                "Link speed" => status.link_speed_mbps = value.trim_end_matches("Mbps").parse().ok(),
+                "Frequency" => status.frequency_mhz = value.trim_end_matches("MHz").parse().ok(),
+                "Supplicant state" => supplicant_completed = value == "COMPLETED",
+                _ => {}
+            }
+        }
+        status.connected = supplicant_completed && status.ssid.is_some();
+        status
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivateDnsMode<'a> {
+    Off,
+    Opportunistic,
+    Hostname(&'a str),
+}
+
+impl PrivateDnsMode<'_> {
+    // Values of Settings.Global.PRIVATE_DNS_MODE.
+    fn setting(&self) -> &'static str {
+        match self {
+            PrivateDnsMode::Off => "off",
+            PrivateDnsMode::Opportunistic => "opportunistic",
+            PrivateDnsMode::Hostname(_) => "hostname",
+        }
+    }
+}
+
+fn private_dns_commands(device: &str, mode: PrivateDnsMode) -> Vec<String> {
+    let mut commands = Vec::new();
+    if let PrivateDnsMode::Hostname(host) = mode {
+        commands.push(format!(
+            "{} shell settings put global private_dns_specifier {}",
+            device,
+            shell_quote(host)
+        ));
+    }
+    commands.push(format!("{} shell settings put global private_dns_mode {}", device, mode.setting()));
+    commands
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiSecurity<'a> {
+    Open,
+    // WPA2-Personal with its passphrase.
+    Wpa2(&'a str),
+    // WPA3-Personal (SAE) with its passphrase.
+    Wpa3(&'a str),
+}
+
+fn connect_network_command(device: &str, ssid: &str, security: WifiSecurity) -> String {
+    let security = match security {
+        WifiSecurity::Open => "open".to_string(),
+        WifiSecurity::Wpa2(psk) => format!("wpa2 {}", shell_quote(psk)),
+        WifiSecurity::Wpa3(psk) => format!("wpa3 {}", shell_quote(psk)),
+    };
+    format!("{} shell cmd wifi connect-network {} {}", device, shell_quote(ssid), security)
+}
+
+fn not_applied(what: &str, enabled: bool) -> io::Error {
+    io::Error::other(format!(
+        "{} did not switch {} (read-back still reports the old state)",
+        what,
+        if enabled { "on" } else { "off" }
+    ))
+}
+
+fn airplane_fallback_commands(device: &str, enabled: bool) -> [String; 2] {
+    let flag = if enabled { 1 } else { 0 };
+    [
+        format!("{} shell settings put global airplane_mode_on {}", device, flag),
+        format!(
+            "{} shell am broadcast -a android.intent.action.AIRPLANE_MODE --ez state {}",
+            device, enabled
+        ),
+    ]
+}
+
+// Newer registries report the user toggle directly as `mUserMobileDataState`;
+// otherwise the first `mDataConnectionState` stands in for it. A suspended
+// connection (e.g. during a call) is still up, so it only satisfies an
+// enable. When neither line is there the state is unknown and verifies
+// nothing, so the caller keeps polling and eventually reports the toggle as
+// not applied.
+fn mobile_data_matches(registry: &str, enabled: bool) -> bool {
+    let lines = || registry.lines().map(str::trim);
+    if let Some(user_state) = lines().find_map(|line| line.strip_prefix("mUserMobileDataState=")) {
+        return user_state.trim() == enabled.to_string();
+    }
+    let state = lines()
+        .find_map(|line| line.strip_prefix("mDataConnectionState="))
+        .map(|code| DataState::from_code(code.trim().parse().unwrap_or(-1)))
+        .unwrap_or_default();
+    match state {
+        DataState::Connected | DataState::Connecting | DataState::Suspended => enabled,
+        DataState::Disconnected => !enabled,
+        DataState::Unknown => false,
+    }
+}
+
+impl ADB {
+    // `svc wifi` stopped working for the shell user on Android 11, where
+    // `cmd wifi` took over.
+    pub fn set_wifi_enabled(&self, device: &str, enabled: bool) -> Result<(), io::Error> {
+        let state = if enabled { "enabled" } else { "disabled" };
+        if self
+            .run_adb(&format!("{} shell cmd wifi set-wifi-enabled {}", device, state))
+            .is_err()
+        {
+            let action = if enabled { "enable" } else { "disable" };
+            self.run_adb(&format!("{} shell svc wifi {}", device, action))?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_wifi_enabled_async(&self, device: &str, enabled: bool) -> Result<(), io::Error> {
+        let state = if enabled { "enabled" } else { "disabled" };
+        if self
+            .run_adb_async(&format!("{} shell cmd wifi set-wifi-enabled {}", device, state))
+            .await
+            .is_err()
+        {
+            let action = if enabled { "enable" } else { "disable" };
+            self.run_adb_async(&format!("{} shell svc wifi {}", device, action))
+                .await?;
+        }
+        Ok(())
+    }
+
+    // `cmd wifi connect-network` exists from API 30.
+    pub fn connect_wifi(&self, device: &str, ssid: &str, security: WifiSecurity) -> Result<(), io::Error> {
+        self.require_api(device, 30)?;
+        self.run_adb(&connect_network_command(device, ssid, security))?;
+        Ok(())
+    }
+
+    pub async fn connect_wifi_async(&self, device: &str, ssid: &str, security: WifiSecurity<'_>) -> Result<(), io::Error> {
+        self.require_api_async(device, 30).await?;
+        self.run_adb_async(&connect_network_command(device, ssid, security))
+            .await?;
+        Ok(())
+    }
+
+    pub fn get_wifi_status(&self, device: &str) -> Result<WifiStatus, io::Error> {
+        let output = match self.run_adb(&format!("{} shell cmd wifi status", device)) {
+            Ok(output) if output.contains("SSID") || output.contains("enabled") || output.contains("disabled") => output,
+            _ => self.run_adb(&format!("{} shell dumpsys wifi", device))?,
+        };
+        Ok(WifiStatus::parse(&output))
+    }
+
+    pub async fn get_wifi_status_async(&self, device: &str) -> Result<WifiStatus, io::Error> {
+        let output = match self
+            .run_adb_async(&format!("{} shell cmd wifi status", device))
+            .await
+        {
+            Ok(output) if output.contains("SSID") || output.contains("enabled") || output.contains("disabled") => output,
+            _ => {
+                self.run_adb_async(&format!("{} shell dumpsys wifi", device))
+                    .await?
+            }
+        };
+        Ok(WifiStatus::parse(&output))
+    }
+
+    pub fn get_airplane_mode(&self, device: &str) -> Result<bool, io::Error> {
+        let output = self.run_adb(&format!("{} shell settings get global airplane_mode_on", device))?;
+        Ok(output.trim() == "1")
+    }
+
+    pub async fn get_airplane_mode_async(&self, device: &str) -> Result<bool, io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell settings get global airplane_mode_on", device))
+            .await?;
+        Ok(output.trim() == "1")
+    }
+
+    // Uses `cmd connectivity airplane-mode` where available. Older builds get
+    // the setting plus broadcast, which some of them reject from the shell,
+    // so the state is read back before reporting success.
+    pub fn set_airplane_mode(&self, device: &str, enabled: bool) -> Result<(), io::Error> {
+        let action = if enabled { "enable" } else { "disable" };
+        if self
+            .run_adb(&format!("{} shell cmd connectivity airplane-mode {}", device, action))
+            .is_err()
+        {
+            let [setting, broadcast] = airplane_fallback_commands(device, enabled);
+            self.run_adb(&setting)?;
+            let _ = self.run_adb(&broadcast);
+        }
+
+        let start = Instant::now();
+        while self.get_airplane_mode(device)? != enabled {
+            if start.elapsed() >= SETTLE_TIMEOUT {
+                return Err(not_applied("Airplane mode", enabled));
+            }
+            std::thread::sleep(SETTLE_POLL);
+        }
+        Ok(())
+    }
+
+    pub async fn set_airplane_mode_async(&self, device: &str, enabled: bool) -> Result<(), io::Error> {
+        let action = if enabled { "enable" } else { "disable" };
+        if self
+            .run_adb_async(&format!("{} shell cmd connectivity airplane-mode {}", device, action))
+            .await
+            .is_err()
+        {
+            let [setting, broadcast] = airplane_fallback_commands(device, enabled);
+            self.run_adb_async(&setting).await?;
+            let _ = self.run_adb_async(&broadcast).await;
+        }
+
+        let start = Instant::now();
+        while self.get_airplane_mode_async(device).await? != enabled {
+            if start.elapsed() >= SETTLE_TIMEOUT {
+                return Err(not_applied("Airplane mode", enabled));
+            }
+            tokio::time::sleep(SETTLE_POLL).await;
+        }
+        Ok(())
+    }
+
+    pub fn set_mobile_data_enabled(&self, device: &str, enabled: bool) -> Result<(), io::Error> {
+        let action = if enabled { "enable" } else { "disable" };
+        self.run_adb(&format!("{} shell svc data {}", device, action))?;
+
+        let start = Instant::now();
+        loop {
+            let registry = self.run_adb(&format!("{} shell dumpsys telephony.registry", device))?;
+            if mobile_data_matches(&registry, enabled) {
+                return Ok(());
+            }
+            if start.elapsed() >= SETTLE_TIMEOUT {
+                return Err(not_applied("Mobile data", enabled));
+            }
+            std::thread::sleep(SETTLE_POLL);
+        }
+    }
+
+    pub async fn set_mobile_data_enabled_async(&self, device: &str, enabled: bool) -> Result<(), io::Error> {
+        let action = if enabled { "enable" } else { "disable" };
+        self.run_adb_async(&format!("{} shell svc data {}", device, action))
+            .await?;
+
+        let start = Instant::now();
+        loop {
+            let registry = self
+                .run_adb_async(&format!("{} shell dumpsys telephony.registry", device))
+                .await?;
+            if mobile_data_matches(&registry, enabled) {
+                return Ok(());
+            }
+            if start.elapsed() >= SETTLE_TIMEOUT {
+                return Err(not_applied("Mobile data", enabled));
+            }
+            tokio::time::sleep(SETTLE_POLL).await;
+        }
+    }
+
+    // The specifier is written before the mode so the resolver never sees
+    // hostname mode with a stale host.
+    pub fn set_private_dns(&self, device: &str, mode: PrivateDnsMode) -> Result<(), io::Error> {
+        for command in private_dns_commands(device, mode) {
+            self.run_adb(&command)?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_private_dns_async(&self, device: &str, mode: PrivateDnsMode<'_>) -> Result<(), io::Error> {
+        for command in private_dns_commands(device, mode) {
+            self.run_adb_async(&command).await?;
+        }
+        Ok(())
+    }
+}
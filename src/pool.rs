@@ -0,0 +1,366 @@
+// src/pool.rs
+
+use crate::{BatchResult, CommandResult, DeviceFilter, Workflow, WorkflowEngine, WorkflowResult, ADB};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::mpsc;
+use tokio::task::JoinSet;
+
+// A fixed set of devices, by serial, that work is spread across.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DevicePool {
+    serials: Vec<String>,
+}
+
+impl DevicePool {
+    pub fn new<I, S>(serials: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        DevicePool {
+            serials: serials.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    // The online devices currently matching `filter`.
+    pub fn from_filter(adb: &ADB, filter: &DeviceFilter) -> Result<Self, io::Error> {
+        Ok(DevicePool::new(filter.select(&adb.refresh_device_list()?)))
+    }
+
+    pub async fn from_filter_async(adb: &ADB, filter: &DeviceFilter) -> Result<Self, io::Error> {
+        Ok(DevicePool::new(filter.select(&adb.refresh_device_list_async().await?)))
+    }
+
+    pub fn serials(&self) -> &[String] {
+        &self.serials
+    }
+
+    pub fn len(&self) -> usize {
+        self.serials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.serials.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardingStrategy {
+    // Runs `n` copies of the whole workflow, each on the next free device.
+    // The instance number, counting from 0, is available as `${shard}`.
+    Instances(usize),
+    // Hands each top-level step to the next free device as a workflow of its
+    // own. Steps then cannot see each other's results, so `${steps.*}` and
+    // conditions between them do not resolve; meant for independent steps
+    // such as one test suite per step. Every step still runs with the
+    // workflow's setup and teardown hooks.
+    Steps,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PoolResult {
+    // Completed runs by serial, in the order each device finished them.
+    pub devices: BTreeMap<String, Vec<WorkflowResult>>,
+    // Devices that went offline during the run. The run they were on is
+    // discarded and handed to another device.
+    pub dropped: Vec<String>,
+    // Work left over when no devices remained, e.g. `instance 3` or a step
+    // name.
+    pub unfinished: Vec<String>,
+}
+
+impl PoolResult {
+    pub fn success(&self) -> bool {
+        self.unfinished.is_empty() && self.results().all(WorkflowResult::success)
+    }
+
+    pub fn results(&self) -> impl Iterator<Item = &WorkflowResult> {
+        self.devices.values().flatten()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FleetBatchResult {
+    pub devices: BTreeMap<String, BatchResult>,
+}
+
+impl FleetBatchResult {
+    pub fn device(&self, serial: &str) -> Option<&BatchResult> {
+        self.devices.get(serial)
+    }
+
+    pub fn success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    // Serials that had at least one failing command.
+    pub fn failed_devices(&self) -> impl Iterator<Item = &str> {
+        self.devices
+            .iter()
+            .filter(|(_, batch)| batch.failures().next().is_some())
+            .map(|(serial, _)| serial.as_str())
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &CommandResult)> {
+        self.devices
+            .iter()
+            .flat_map(|(serial, batch)| batch.failures().map(move |command| (serial.as_str(), command)))
+    }
+}
+
+struct PoolUnit {
+    label: String,
+    workflow: Workflow,
+}
+
+impl ShardingStrategy {
+    fn units(&self, workflow: &Workflow) -> VecDeque<PoolUnit> {
+        match self {
+            ShardingStrategy::Instances(count) => (0..*count)
+                .map(|index| PoolUnit {
+                    label: format!("instance {}", index),
+                    workflow: workflow.clone().with_var("shard", &index.to_string()),
+                })
+                .collect(),
+            ShardingStrategy::Steps => workflow
+                .steps
+                .iter()
+                .map(|step| PoolUnit {
+                    label: step.name.clone(),
+                    workflow: Workflow {
+                        steps: vec![step.clone()],
+                        ..workflow.clone()
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+// Queue bookkeeping shared by the sync and async runners: which devices are
+// free, what is left to run and what came back.
+struct PoolRun {
+    pending: VecDeque<PoolUnit>,
+    idle: Vec<String>,
+    busy: usize,
+    result: PoolResult,
+}
+
+impl PoolRun {
+    fn new(pool: &DevicePool, units: VecDeque<PoolUnit>) -> Result<Self, io::Error> {
+        if pool.is_empty() {
+            return Err(empty_pool());
+        }
+        // Popped from the back, so the first serial gets the first unit.
+        let idle = pool.serials.iter().rev().cloned().collect();
+        Ok(PoolRun {
+            pending: units,
+            idle,
+            busy: 0,
+            result: PoolResult::default(),
+        })
+    }
+
+    fn next(&mut self) -> Option<(String, PoolUnit)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let serial = self.idle.pop()?;
+        let unit = self.pending.pop_front()?;
+        self.busy += 1;
+        Some((serial, unit))
+    }
+
+    fn complete(&mut self, serial: String, unit: PoolUnit, outcome: Result<WorkflowResult, io::Error>, online: bool) {
+        self.busy -= 1;
+        if !online {
+            self.result.dropped.push(serial);
+            self.pending.push_front(unit);
+            return;
+        }
+        match outcome {
+            Ok(result) => self.result.devices.entry(serial.clone()).or_default().push(result),
+            Err(_) => self.result.unfinished.push(unit.label),
+        }
+        self.idle.push(serial);
+    }
+
+    fn finish(mut self) -> PoolResult {
+        self.result
+            .unfinished
+            .extend(self.pending.into_iter().map(|unit| unit.label));
+        self.result
+    }
+}
+
+fn empty_pool() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "Device pool is empty")
+}
+
+// Only a failed run is worth a `get-state` round-trip to tell a real
+// failure from a device that went away.
+fn needs_state_check(outcome: &Result<WorkflowResult, io::Error>) -> bool {
+    !matches!(outcome, Ok(result) if result.success())
+}
+
+impl ADB {
+    pub(crate) fn is_online(&self, device: &str) -> bool {
+        self.run_adb(&format!("{} get-state", device))
+            .is_ok_and(|state| state.trim() == "device")
+    }
+
+    pub(crate) async fn is_online_async(&self, device: &str) -> bool {
+        self.run_adb_async(&format!("{} get-state", device))
+            .await
+            .is_ok_and(|state| state.trim() == "device")
+    }
+
+    // Spreads the workflow over the pool, one run per device at a time. When
+    // a run fails and its device no longer reports `device` state, the device
+    // leaves the pool and the run is queued again for the others. Workflows
+    // with custom step types or observers go through
+    // `WorkflowEngine::execute_workflow_on_pool` instead.
+    pub fn execute_workflow_on_pool(
+        &self,
+        workflow: &Workflow,
+        pool: &DevicePool,
+        strategy: ShardingStrategy,
+    ) -> Result<PoolResult, io::Error> {
+        WorkflowEngine::new(self).execute_workflow_on_pool(workflow, pool, strategy)
+    }
+
+    pub async fn execute_workflow_on_pool_async(
+        &self,
+        workflow: &Workflow,
+        pool: &DevicePool,
+        strategy: ShardingStrategy,
+    ) -> Result<PoolResult, io::Error> {
+        WorkflowEngine::new(self)
+            .execute_workflow_on_pool_async(workflow, pool, strategy)
+            .await
+    }
+
+    // Runs the same batch on every device of the pool at once, each device
+    // working through the commands in order.
+    pub fn execute_batch_commands_on_pool(&self, pool: &DevicePool, commands: &[&str]) -> Result<FleetBatchResult, io::Error> {
+        if pool.is_empty() {
+            return Err(empty_pool());
+        }
+        let devices = std::thread::scope(|scope| {
+            let tasks: Vec<_> = pool
+                .serials
+                .iter()
+                .map(|serial| {
+                    let task = scope.spawn(move || self.execute_batch_commands(&format!("-s {}", serial), commands));
+                    (serial, task)
+                })
+                .collect();
+            tasks
+                .into_iter()
+                .map(|(serial, task)| {
+                    let batch = task.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+                    Ok((serial.clone(), batch))
+                })
+                .collect::<Result<_, io::Error>>()
+        })?;
+        Ok(FleetBatchResult { devices })
+    }
+
+    pub async fn execute_batch_commands_on_pool_async(
+        &self,
+        pool: &DevicePool,
+        commands: &[&str],
+    ) -> Result<FleetBatchResult, io::Error> {
+        if pool.is_empty() {
+            return Err(empty_pool());
+        }
+        let commands: Vec<String> = commands.iter().map(|command| command.to_string()).collect();
+        let mut tasks = JoinSet::new();
+        for serial in &pool.serials {
+            let adb = self.clone();
+            let serial = serial.clone();
+            let commands = commands.clone();
+            tasks.spawn(async move {
+                let commands: Vec<&str> = commands.iter().map(String::as_str).collect();
+                let batch = adb
+                    .execute_batch_commands_async(&format!("-s {}", serial), &commands)
+                    .await;
+                (serial, batch)
+            });
+        }
+        let mut devices = BTreeMap::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((serial, batch)) => {
+                    devices.insert(serial, batch?);
+                }
+                Err(error) => std::panic::resume_unwind(error.into_panic()),
+            }
+        }
+        Ok(FleetBatchResult { devices })
+    }
+}
+
+impl WorkflowEngine {
+    // Like `ADB::execute_workflow_on_pool`, running each unit with this
+    // engine's step types and observers.
+    pub fn execute_workflow_on_pool(
+        &self,
+        workflow: &Workflow,
+        pool: &DevicePool,
+        strategy: ShardingStrategy,
+    ) -> Result<PoolResult, io::Error> {
+        let mut run = PoolRun::new(pool, strategy.units(workflow))?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::scope(|scope| loop {
+            while let Some((serial, unit)) = run.next() {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let device = format!("-s {}", serial);
+                    let outcome = self.execute_workflow(&device, &unit.workflow);
+                    let online = !needs_state_check(&outcome) || self.adb().is_online(&device);
+                    let _ = sender.send((serial, unit, outcome, online));
+                });
+            }
+            if run.busy == 0 {
+                break;
+            }
+            let Ok((serial, unit, outcome, online)) = receiver.recv() else {
+                break;
+            };
+            run.complete(serial, unit, outcome, online);
+        });
+        Ok(run.finish())
+    }
+
+    pub async fn execute_workflow_on_pool_async(
+        &self,
+        workflow: &Workflow,
+        pool: &DevicePool,
+        strategy: ShardingStrategy,
+    ) -> Result<PoolResult, io::Error> {
+        let mut run = PoolRun::new(pool, strategy.units(workflow))?;
+        let mut tasks = JoinSet::new();
+        loop {
+            while let Some((serial, unit)) = run.next() {
+                let engine = self.clone();
+                tasks.spawn(async move {
+                    let device = format!("-s {}", serial);
+                    let outcome = engine.execute_workflow_async(&device, &unit.workflow).await;
+                    let online = !needs_state_check(&outcome) || engine.adb().is_online_async(&device).await;
+                    (serial, unit, outcome, online)
+                });
+            }
+            let Some(joined) = tasks.join_next().await else {
+                break;
+            };
+            match joined {
+                Ok((serial, unit, outcome, online)) => run.complete(serial, unit, outcome, online),
+                Err(error) => std::panic::resume_unwind(error.into_panic()),
+            }
+        }
+        Ok(run.finish())
+    }
+}
@@ -0,0 +1,113 @@
+// src/power.rs
+
+use crate::dumpsys::{parse_duration, parse_package_uids};
+use crate::ADB;
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WakelockInfo {
+    pub level: String,
+    pub tag: String,
+    pub flags: Vec<String>,
+    pub held_for: Option<Duration>,
+    pub uid: Option<u32>,
+    pub pid: Option<u32>,
+    pub work_source_uids: Vec<u32>,
+    // Packages owning `uid` and the work source uids.
+    pub packages: Vec<String>,
+}
+
+impl WakelockInfo {
+    pub fn is_partial(&self) -> bool {
+        self.level == "PARTIAL_WAKE_LOCK"
+    }
+}
+
+// Parses one entry of the `Wake Locks:` section of `dumpsys power`:
+// `PARTIAL_WAKE_LOCK 'AudioMix' ACQ=-5s12ms (uid=1041 pid=1234 ws=WorkSource{10123})`.
+fn parse_wakelock(line: &str) -> Option<WakelockInfo> {
+    let (level, rest) = line.split_once(' ')?;
+    let rest = rest.trim_start().strip_prefix('\'')?;
+    let (tag, rest) = rest.split_once('\'')?;
+    let (attributes, owner) = rest.split_once('(').unwrap_or((rest, ""));
+
+    let mut info = WakelockInfo {
+        level: level.to_string(),
+        tag: tag.to_string(),
+        ..WakelockInfo::default()
+    };
+    for token in attributes.split_whitespace() {
+        match token.strip_prefix("ACQ=") {
+            Some(acquired) => info.held_for = parse_duration(acquired),
+            None => info.flags.push(token.to_string()),
+        }
+    }
+    for token in owner.trim_end_matches(')').split_whitespace() {
+        if let Some(uid) = token.strip_prefix("uid=") {
+            info.uid = uid.parse().ok();
+        } else if let Some(pid) = token.strip_prefix("pid=") {
+            info.pid = pid.parse().ok();
+        } else if let Some(work_source) = token.strip_prefix("ws=WorkSource{") {
+            info.work_source_uids = work_source
+                .trim_end_matches('}')
+                .split(',')
+                .filter_map(|uid| uid.split_whitespace().next()?.parse().ok())
+                .collect();
+        }
+    }
+    Some(info)
+}
+
+fn parse_wakelocks(power: &str, packages: &HashMap<u32, Vec<String>>) -> Vec<WakelockInfo> {
+    let mut wakelocks = Vec::new();
+    let mut in_section = false;
+    for line in power.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Wake Locks:") {
+            in_section = true;
+            continue;
+        }
+        if !in_section {
+            continue;
+        }
+        match parse_wakelock(trimmed) {
+            Some(mut wakelock) => {
+                for uid in wakelock.uid.iter().chain(&wakelock.work_source_uids) {
+                    for package in packages.get(uid).into_iter().flatten() {
+                        if !wakelock.packages.contains(package) {
+                            wakelock.packages.push(package.clone());
+                        }
+                    }
+                }
+                wakelocks.push(wakelock);
+            }
+            None => break,
+        }
+    }
+    wakelocks
+}
+
+impl ADB {
+    pub fn get_wakelocks(&self, device: &str) -> Result<Vec<WakelockInfo>, io::Error> {
+        let power = self.run_adb(&format!("{} shell dumpsys power", device))?;
+        // Uid mapping needs `pm list packages -U` (Android 8+); without it
+        // wakelocks are still reported, just without package names.
+        let packages = self
+            .run_adb(&format!("{} shell pm list packages -U", device))
+            .map(|output| parse_package_uids(&output))
+            .unwrap_or_default();
+        Ok(parse_wakelocks(&power, &packages))
+    }
+
+    pub async fn get_wakelocks_async(&self, device: &str) -> Result<Vec<WakelockInfo>, io::Error> {
+        let power = self.run_adb_async(&format!("{} shell dumpsys power", device)).await?;
+        let packages = self
+            .run_adb_async(&format!("{} shell pm list packages -U", device))
+            .await
+            .map(|output| parse_package_uids(&output))
+            .unwrap_or_default();
+        Ok(parse_wakelocks(&power, &packages))
+    }
+}
@@ -0,0 +1,189 @@
+// src/process.rs
+
+use crate::ADB;
+use futures::stream::{self, Stream};
+use std::io;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessSample {
+    pub pid: u32,
+    pub elapsed: Duration,
+    // Percent of one core, like `top`; None for the first sample since it
+    // needs a previous reading to diff against.
+    pub cpu_pct: Option<f32>,
+    pub rss_kb: u64,
+    // Needs /proc/<pid>/smaps_rollup (kernel 4.14+) to be readable.
+    pub pss_kb: Option<u64>,
+    pub threads: u32,
+    // Listing /proc/<pid>/fd usually needs root or a debuggable app.
+    pub fd_count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CpuTicks {
+    process: u64,
+    total: u64,
+}
+
+fn sample_script(pid: u32) -> String {
+    format!(
+        "cat /proc/{pid}/stat; grep '^cpu' /proc/stat; grep -E '^(VmRSS|Threads):' /proc/{pid}/status; \
+         grep '^Pss:' /proc/{pid}/smaps_rollup 2>/dev/null; echo fds $(ls /proc/{pid}/fd 2>/dev/null | wc -l)",
+        pid = pid
+    )
+}
+
+fn kb_field(value: &str) -> Option<u64> {
+    value.split_whitespace().next()?.parse().ok()
+}
+
+fn parse_sample(
+    output: &str,
+    pid: u32,
+    elapsed: Duration,
+    previous: Option<CpuTicks>,
+) -> Result<(ProcessSample, CpuTicks), io::Error> {
+    let mut ticks = None;
+    let mut total = 0u64;
+    let mut cores = 0u32;
+    let mut sample = ProcessSample {
+        pid,
+        elapsed,
+        cpu_pct: None,
+        rss_kb: 0,
+        pss_kb: None,
+        threads: 0,
+        fd_count: None,
+    };
+
+    let stat_prefix = format!("{} (", pid);
+    for line in output.lines() {
+        if line.starts_with(&stat_prefix) {
+            // Fields after the `(comm)`; utime and stime are the 14th and
+            // 15th fields of the whole line.
+            let fields: Vec<&str> = line
+                .rsplit_once(')')
+                .map(|(_, rest)| rest.split_whitespace().collect())
+                .unwrap_or_default();
+            if let (Some(utime), Some(stime)) = (fields.get(11), fields.get(12)) {
+                ticks = Some(utime.parse::<u64>().unwrap_or(0) + stime.parse::<u64>().unwrap_or(0));
+            }
+        } else if let Some(rest) = line.strip_prefix("cpu") {
+            if rest.starts_with(' ') {
+                total = rest.split_whitespace().filter_map(|value| value.parse::<u64>().ok()).sum();
+            } else {
+                cores += 1;
+            }
+        } else if let Some(value) = line.strip_prefix("VmRSS:") {
+            sample.rss_kb = kb_field(value).unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("Threads:") {
+            sample.threads = kb_field(value).unwrap_or(0) as u32;
+        } else if let Some(value) = line.strip_prefix("Pss:") {
+            sample.pss_kb = kb_field(value);
+        } else if let Some(value) = line.strip_prefix("fds ") {
+            // A live process always has open descriptors, so 0 means the
+            // listing was denied.
+            sample.fd_count = value.trim().parse().ok().filter(|&count| count > 0);
+        }
+    }
+
+    let process = ticks.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("Process {} is no longer running", pid))
+    })?;
+    let current = CpuTicks { process, total };
+    if let Some(previous) = previous {
+        let total_delta = current.total.saturating_sub(previous.total);
+        if total_delta > 0 {
+            let process_delta = current.process.saturating_sub(previous.process);
+            sample.cpu_pct = Some(process_delta as f32 / total_delta as f32 * 100.0 * cores.max(1) as f32);
+        }
+    }
+    Ok((sample, current))
+}
+
+fn parse_pid(output: &str, package: &str) -> Result<u32, io::Error> {
+    output
+        .split_whitespace()
+        .next()
+        .and_then(|pid| pid.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} is not running", package)))
+}
+
+struct MonitorState {
+    pid: Option<u32>,
+    previous: Option<CpuTicks>,
+    ticker: Option<tokio::time::Interval>,
+    started: Instant,
+    done: bool,
+}
+
+impl ADB {
+    pub fn get_pid(&self, device: &str, package: &str) -> Result<u32, io::Error> {
+        let output = self.run_adb(&format!("{} shell pidof {}", device, package));
+        parse_pid(&output.unwrap_or_default(), package)
+    }
+
+    pub async fn get_pid_async(&self, device: &str, package: &str) -> Result<u32, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell pidof {}", device, package)).await;
+        parse_pid(&output.unwrap_or_default(), package)
+    }
+
+    // Samples the package's main process every `interval`. The stream ends
+    // after yielding an error once the process has exited; other errors are
+    // yielded and sampling continues.
+    pub fn monitor_process<'a>(
+        &'a self,
+        device: &'a str,
+        package: &'a str,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<ProcessSample, io::Error>> + 'a {
+        let interval = interval.max(Duration::from_millis(1));
+        let state = MonitorState {
+            pid: None,
+            previous: None,
+            ticker: None,
+            started: Instant::now(),
+            done: false,
+        };
+        stream::unfold(state, move |mut state| async move {
+            if state.done {
+                return None;
+            }
+            state
+                .ticker
+                .get_or_insert_with(|| tokio::time::interval(interval))
+                .tick()
+                .await;
+
+            let pid = match state.pid {
+                Some(pid) => pid,
+                None => match self.get_pid_async(device, package).await {
+                    Ok(pid) => *state.pid.insert(pid),
+                    Err(err) => {
+                        state.done = true;
+                        return Some((Err(err), state));
+                    }
+                },
+            };
+            let result = match self
+                .run_adb_async(&format!("{} shell {}", device, sample_script(pid)))
+                .await
+            {
+                Ok(output) => parse_sample(&output, pid, state.started.elapsed(), state.previous),
+                Err(err) => Err(err),
+            };
+            let item = match result {
+                Ok((sample, ticks)) => {
+                    state.previous = Some(ticks);
+                    Ok(sample)
+                }
+                Err(err) => {
+                    state.done = err.kind() == io::ErrorKind::NotFound;
+                    Err(err)
+                }
+            };
+            Some((item, state))
+        })
+    }
+}
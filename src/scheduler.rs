@@ -0,0 +1,435 @@
+// src/scheduler.rs
+
+use crate::{Workflow, WorkflowEngine, WorkflowResult, ADB};
+use futures::future::{BoxFuture, FutureExt};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+// The loop wakes up at least this often, so wall-clock jumps are noticed.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    // Runs right away, then every interval after the previous start.
+    Every(Duration),
+    Cron(CronSchedule),
+}
+
+// A five-field cron expression (`minute hour day-of-month month
+// day-of-week`) evaluated in UTC. Fields accept `*`, lists, ranges and
+// `/step`; Sunday is 0. As in cron, when both day fields are restricted a
+// day matching either one is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<u64, io::Error> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid cron field: {}", field));
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's
+// civil_from_days.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl FromStr for CronSchedule {
+    type Err = io::Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cron expressions need five fields: {}", expression),
+            ));
+        };
+        Ok(CronSchedule {
+            minutes: parse_cron_field(minute, 0, 59)?,
+            hours: parse_cron_field(hour, 0, 23)?,
+            days: parse_cron_field(day, 1, 31)?,
+            months: parse_cron_field(month, 1, 12)?,
+            // 7 is accepted as Sunday too.
+            weekdays: {
+                let mask = parse_cron_field(weekday, 0, 7)?;
+                (mask | (mask >> 7)) & 0x7f
+            },
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+impl CronSchedule {
+    fn matches(&self, minutes_since_epoch: i64) -> bool {
+        let days = minutes_since_epoch.div_euclid(1440);
+        let minute_of_day = minutes_since_epoch.rem_euclid(1440);
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7);
+        let day_matches = self.days & (1 << day) != 0;
+        let weekday_matches = self.weekdays & (1 << weekday) != 0;
+        let day_ok = match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day_matches,
+            (true, false) => weekday_matches,
+            (false, false) => day_matches || weekday_matches,
+        };
+        self.minutes & (1 << (minute_of_day % 60)) != 0
+            && self.hours & (1 << (minute_of_day / 60)) != 0
+            && self.months & (1 << month) != 0
+            && day_ok
+    }
+
+    // The first matching minute strictly after `after`, searched up to
+    // about four years ahead (enough for a 29 February schedule).
+    pub fn next_after(&self, after: SystemTime) -> Option<SystemTime> {
+        let minutes = after.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64 / 60;
+        (minutes + 1..minutes + 4 * 366 * 1440)
+            .find(|minute| self.matches(*minute))
+            .map(|minute| UNIX_EPOCH + Duration::from_secs(minute as u64 * 60))
+    }
+}
+
+impl Schedule {
+    pub fn cron(expression: &str) -> Result<Self, io::Error> {
+        Ok(Schedule::Cron(expression.parse()?))
+    }
+
+    // Without a last run, the schedule counts from `armed_at`: an interval
+    // task is due straight away, a cron task at its first match after it.
+    fn next_due(&self, last_run: Option<SystemTime>, armed_at: SystemTime) -> Option<SystemTime> {
+        match (self, last_run) {
+            (Schedule::Every(_), None) => Some(armed_at),
+            (Schedule::Every(interval), Some(last)) => Some(last + *interval),
+            (Schedule::Cron(cron), last) => cron.next_after(last.unwrap_or(armed_at)),
+        }
+    }
+}
+
+// Receives the serial and the full `devices -l` line (model, product, ...).
+pub type DeviceMatcher = Arc<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+// Which attached devices a task runs on; matched against `adb devices -l`.
+#[derive(Clone)]
+pub enum DeviceFilter {
+    All,
+    Serials(Vec<String>),
+    Matching(DeviceMatcher),
+}
+
+impl DeviceFilter {
+    pub(crate) fn select(&self, listing: &[String]) -> Vec<String> {
+        listing
+            .iter()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let serial = fields.next()?;
+                if fields.next() != Some("device") {
+                    return None;
+                }
+                let selected = match self {
+                    DeviceFilter::All => true,
+                    DeviceFilter::Serials(serials) => serials.iter().any(|wanted| wanted == serial),
+                    DeviceFilter::Matching(filter) => filter(serial, line),
+                };
+                selected.then(|| serial.to_string())
+            })
+            .collect()
+    }
+}
+
+type TaskFn = Arc<dyn Fn(ADB, String) -> BoxFuture<'static, Result<(), io::Error>> + Send + Sync>;
+
+#[derive(Clone)]
+enum TaskAction {
+    Workflow(Arc<Workflow>),
+    Closure(TaskFn),
+}
+
+struct ScheduledTask {
+    name: String,
+    schedule: Schedule,
+    filter: DeviceFilter,
+    action: TaskAction,
+    // When the task was added, or the scheduler started if later.
+    armed_at: SystemTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedulerEvent {
+    Started { task: String, device: String },
+    Finished {
+        task: String,
+        device: String,
+        success: bool,
+        error: Option<String>,
+        workflow: Option<WorkflowResult>,
+    },
+    // The previous run of this task on this device had not finished yet.
+    SkippedOverlap { task: String, device: String },
+    // Listing devices or saving the state file failed; the scheduler keeps
+    // running.
+    Error { task: String, error: String },
+}
+
+// Runs workflows and closures on schedules against filtered devices. Runs
+// happen on their own tokio tasks, so a slow task only delays itself, and a
+// task never runs twice at once on the same device. Last-run times can be
+// kept in a JSON state file so interval tasks survive restarts.
+pub struct Scheduler {
+    adb: ADB,
+    // Runs the workflow tasks, so registered step types and observers apply.
+    engine: WorkflowEngine,
+    tasks: Vec<ScheduledTask>,
+    state_path: Option<PathBuf>,
+    // Milliseconds since the Unix epoch, by task name.
+    last_runs: HashMap<String, u64>,
+}
+
+impl Scheduler {
+    pub fn new(adb: &ADB) -> Self {
+        Scheduler::with_engine(&WorkflowEngine::new(adb))
+    }
+
+    // Workflow tasks run through `engine`, with its custom step types and
+    // observers.
+    pub fn with_engine(engine: &WorkflowEngine) -> Self {
+        Scheduler {
+            adb: engine.adb().clone(),
+            engine: engine.clone(),
+            tasks: Vec::new(),
+            state_path: None,
+            last_runs: HashMap::new(),
+        }
+    }
+
+    // Loads last-run times from `path` if it exists and saves them there
+    // whenever a task starts.
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Result<Self, io::Error> {
+        let path = path.into();
+        if path.exists() {
+            let text = fs::read_to_string(&path)?;
+            self.last_runs = serde_json::from_str(&text)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid scheduler state: {}", error)))?;
+        }
+        self.state_path = Some(path);
+        Ok(self)
+    }
+
+    pub fn add_workflow(&mut self, name: &str, schedule: Schedule, filter: DeviceFilter, workflow: Workflow) {
+        self.tasks.push(ScheduledTask {
+            name: name.to_string(),
+            schedule,
+            filter,
+            action: TaskAction::Workflow(Arc::new(workflow)),
+            armed_at: SystemTime::now(),
+        });
+    }
+
+    // `task` receives a handle to adb and the `-s <serial>` device argument.
+    pub fn add_task<F, Fut>(&mut self, name: &str, schedule: Schedule, filter: DeviceFilter, task: F)
+    where
+        F: Fn(ADB, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), io::Error>> + Send + 'static,
+    {
+        let task: TaskFn = Arc::new(move |adb, device| task(adb, device).boxed());
+        self.tasks.push(ScheduledTask {
+            name: name.to_string(),
+            schedule,
+            filter,
+            action: TaskAction::Closure(task),
+            armed_at: SystemTime::now(),
+        });
+    }
+
+    pub fn last_run(&self, task: &str) -> Option<SystemTime> {
+        self.last_runs
+            .get(task)
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(*millis))
+    }
+
+    pub fn next_run(&self, task: &str) -> Option<SystemTime> {
+        let scheduled = self.tasks.iter().find(|scheduled| scheduled.name == task)?;
+        scheduled.schedule.next_due(self.last_run(task), scheduled.armed_at)
+    }
+
+    fn save_state(&self) -> Result<(), io::Error> {
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+        let text = serde_json::to_string_pretty(&self.last_runs).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    // Starts the scheduler loop on a tokio task. Must be called from within
+    // a tokio runtime.
+    pub fn start(self) -> SchedulerHandle {
+        let stop = CancellationToken::new();
+        let (events, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(self.run(stop.clone(), events));
+        SchedulerHandle { stop, events: receiver }
+    }
+
+    async fn run(mut self, stop: CancellationToken, events: mpsc::UnboundedSender<SchedulerEvent>) {
+        let running: Arc<Mutex<HashSet<(String, String)>>> = Arc::default();
+        let started_at = SystemTime::now();
+        for task in &mut self.tasks {
+            task.armed_at = task.armed_at.max(started_at);
+        }
+        while !stop.is_cancelled() {
+            let now = SystemTime::now();
+            for index in 0..self.tasks.len() {
+                let task = &self.tasks[index];
+                let due = task.schedule.next_due(self.last_run(&task.name), task.armed_at);
+                if due.is_none_or(|due| due > now) {
+                    continue;
+                }
+                let name = task.name.clone();
+                let filter = task.filter.clone();
+                let action = task.action.clone();
+                let started = now
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_millis() as u64)
+                    .unwrap_or_default();
+                self.last_runs.insert(name.clone(), started);
+                if let Err(error) = self.save_state() {
+                    let _ = events.send(SchedulerEvent::Error {
+                        task: name.clone(),
+                        error: error.to_string(),
+                    });
+                }
+
+                let listing = match self.adb.refresh_device_list_async().await {
+                    Ok(listing) => listing,
+                    Err(error) => {
+                        let _ = events.send(SchedulerEvent::Error {
+                            task: name,
+                            error: error.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                for serial in filter.select(&listing) {
+                    let key = (name.clone(), serial.clone());
+                    if !running.lock().unwrap().insert(key.clone()) {
+                        let _ = events.send(SchedulerEvent::SkippedOverlap {
+                            task: name.clone(),
+                            device: serial,
+                        });
+                        continue;
+                    }
+                    let _ = events.send(SchedulerEvent::Started {
+                        task: name.clone(),
+                        device: serial.clone(),
+                    });
+                    let adb = self.adb.clone();
+                    let engine = self.engine.clone();
+                    let action = action.clone();
+                    let events = events.clone();
+                    let running = running.clone();
+                    tokio::spawn(async move {
+                        let device = format!("-s {}", serial);
+                        let (result, workflow) = match action {
+                            TaskAction::Workflow(workflow) => match engine.execute_workflow_async(&device, &workflow).await {
+                                Ok(result) if result.success() => (Ok(()), Some(result)),
+                                Ok(result) => (Err(io::Error::other("Workflow failed")), Some(result)),
+                                Err(error) => (Err(error), None),
+                            },
+                            TaskAction::Closure(task) => (task(adb, device).await, None),
+                        };
+                        running.lock().unwrap().remove(&key);
+                        let _ = events.send(SchedulerEvent::Finished {
+                            task: key.0,
+                            device: key.1,
+                            success: result.is_ok(),
+                            error: result.err().map(|error| error.to_string()),
+                            workflow,
+                        });
+                    });
+                }
+            }
+
+            let now = SystemTime::now();
+            let sleep = self
+                .tasks
+                .iter()
+                .filter_map(|task| task.schedule.next_due(self.last_run(&task.name), task.armed_at))
+                .map(|due| due.duration_since(now).unwrap_or_default())
+                .min()
+                .unwrap_or(MAX_SLEEP)
+                .clamp(Duration::from_millis(100), MAX_SLEEP);
+            tokio::select! {
+                _ = tokio::time::sleep(sleep) => {}
+                _ = stop.cancelled() => {}
+            }
+        }
+    }
+}
+
+pub struct SchedulerHandle {
+    stop: CancellationToken,
+    events: mpsc::UnboundedReceiver<SchedulerEvent>,
+}
+
+impl SchedulerHandle {
+    // Stops scheduling new runs; runs already in progress finish on their own.
+    pub fn stop(&self) {
+        self.stop.cancel();
+    }
+
+    pub async fn next_event(&mut self) -> Option<SchedulerEvent> {
+        self.events.recv().await
+    }
+}
@@ -0,0 +1,474 @@
+// src/screen.rs
+
+use crate::ADB;
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::{FutureExt, Stream};
+use std::fmt;
+use std::future::Future;
+use std::io::{self, Read};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::{Child as AsyncChild, ChildStdout, Command as AsyncCommand};
+use tokio_util::io::ReaderStream;
+
+const FINALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const STREAM_CHUNK: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScreenRecordProgress {
+    Started,
+    Stopping,
+    Finalizing { waited: Duration },
+    Pulling,
+    Completed(PathBuf),
+    // Stopping or pulling failed where the error cannot be returned, e.g.
+    // while a scoped recording unwinds from a panic.
+    Failed(String),
+}
+
+pub type ScreenRecordProgressCallback = Arc<dyn Fn(ScreenRecordProgress) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct ScreenRecordOptions {
+    pub bit_rate: Option<u32>,
+    pub size: Option<(u32, u32)>,
+    pub time_limit: Option<Duration>,
+    pub bugreport: bool,
+    pub remote_path: Option<String>,
+    // The library never writes to the terminal; embedders that want a
+    // spinner or status line drive it from here.
+    pub on_progress: Option<ScreenRecordProgressCallback>,
+}
+
+impl fmt::Debug for ScreenRecordOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScreenRecordOptions")
+            .field("bit_rate", &self.bit_rate)
+            .field("size", &self.size)
+            .field("time_limit", &self.time_limit)
+            .field("bugreport", &self.bugreport)
+            .field("remote_path", &self.remote_path)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+impl ScreenRecordOptions {
+    fn to_command(&self, output: &str) -> String {
+        let mut args = vec!["screenrecord".to_string()];
+        if let Some(bit_rate) = self.bit_rate {
+            args.push(format!("--bit-rate {}", bit_rate));
+        }
+        if let Some((width, height)) = self.size {
+            args.push(format!("--size {}x{}", width, height));
+        }
+        if let Some(time_limit) = self.time_limit {
+            args.push(format!("--time-limit {}", time_limit.as_secs().max(1)));
+        }
+        if self.bugreport {
+            args.push("--bugreport".to_string());
+        }
+        args.push(output.to_string());
+        args.join(" ")
+    }
+
+    fn remote_path(&self) -> String {
+        self.remote_path.clone().unwrap_or_else(|| {
+            let stamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis())
+                .unwrap_or_default();
+            format!("/sdcard/rust-adb-record-{}.mp4", stamp)
+        })
+    }
+}
+
+// A screenrecord running in the background. `stop` interrupts it on the
+// device so the mp4 is finalized, pulls it to `local_path` and removes the
+// remote copy. Dropping the handle without stopping discards the recording.
+pub struct ScreenRecordHandle<'a> {
+    adb: &'a ADB,
+    device: String,
+    remote_path: String,
+    local_path: PathBuf,
+    child: Option<Child>,
+    on_progress: Option<ScreenRecordProgressCallback>,
+}
+
+impl ScreenRecordHandle<'_> {
+    fn report(&self, progress: ScreenRecordProgress) {
+        if let Some(on_progress) = &self.on_progress {
+            on_progress(progress);
+        }
+    }
+
+    pub fn remote_path(&self) -> &str {
+        &self.remote_path
+    }
+
+    pub fn local_path(&self) -> &Path {
+        &self.local_path
+    }
+
+    fn interrupt_command(&self) -> String {
+        format!("{} shell pkill -INT -f {}", self.device, self.remote_path)
+    }
+
+    fn cleanup_command(&self) -> String {
+        format!("{} shell rm -f {}", self.device, self.remote_path)
+    }
+
+    fn try_finished(child: &mut Child, start: Instant) -> Result<bool, io::Error> {
+        if child.try_wait()?.is_some() {
+            return Ok(true);
+        }
+        if start.elapsed() >= FINALIZE_TIMEOUT {
+            child.kill()?;
+            child.wait()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    pub fn stop(mut self) -> Result<PathBuf, io::Error> {
+        let mut child = self.child.take().ok_or_else(|| io::Error::other("Recording already stopped"))?;
+        self.report(ScreenRecordProgress::Stopping);
+        if child.try_wait()?.is_none() {
+            // pkill fails when screenrecord already exited on its time limit.
+            let _ = self.adb.run_adb(&self.interrupt_command());
+        }
+        let start = Instant::now();
+        while !Self::try_finished(&mut child, start)? {
+            self.report(ScreenRecordProgress::Finalizing { waited: start.elapsed() });
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        self.report(ScreenRecordProgress::Pulling);
+        let local = self.local_path.to_string_lossy().to_string();
+        self.adb.pull(&self.device, &self.remote_path, &local)?;
+        self.adb.run_adb(&self.cleanup_command())?;
+        self.report(ScreenRecordProgress::Completed(self.local_path.clone()));
+        Ok(self.local_path.clone())
+    }
+
+    pub async fn stop_async(mut self) -> Result<PathBuf, io::Error> {
+        let mut child = self.child.take().ok_or_else(|| io::Error::other("Recording already stopped"))?;
+        self.report(ScreenRecordProgress::Stopping);
+        if child.try_wait()?.is_none() {
+            let _ = self.adb.run_adb_async(&self.interrupt_command()).await;
+        }
+        let start = Instant::now();
+        while !Self::try_finished(&mut child, start)? {
+            self.report(ScreenRecordProgress::Finalizing { waited: start.elapsed() });
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        self.report(ScreenRecordProgress::Pulling);
+        let local = self.local_path.to_string_lossy().to_string();
+        self.adb.pull_async(&self.device, &self.remote_path, &local).await?;
+        self.adb.run_adb_async(&self.cleanup_command()).await?;
+        self.report(ScreenRecordProgress::Completed(self.local_path.clone()));
+        Ok(self.local_path.clone())
+    }
+}
+
+impl ScreenRecordHandle<'_> {
+    // Stops the recording and deletes it on the device, what dropping the
+    // handle does, but with errors reported.
+    pub fn discard(mut self) -> Result<(), io::Error> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        let _ = self.adb.run_adb(&self.interrupt_command());
+        let _ = child.kill();
+        child.wait()?;
+        self.adb.run_adb(&self.cleanup_command())?;
+        Ok(())
+    }
+
+    // Like `discard`, without blocking the executor on the adb calls.
+    pub async fn discard_async(mut self) -> Result<(), io::Error> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        let _ = self.adb.run_adb_async(&self.interrupt_command()).await;
+        let _ = child.kill();
+        while child.try_wait()?.is_none() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        self.adb.run_adb_async(&self.cleanup_command()).await?;
+        Ok(())
+    }
+}
+
+impl Drop for ScreenRecordHandle<'_> {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = self.adb.run_adb(&self.interrupt_command());
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = self.adb.run_adb(&self.cleanup_command());
+        }
+    }
+}
+
+fn stream_command(device: &str, options: &ScreenRecordOptions) -> String {
+    format!("{} exec-out {}", device, options.to_command("--output-format=h264 -"))
+}
+
+// Raw H.264 elementary stream from `screenrecord --output-format=h264`.
+// Dropping the stream kills the underlying adb process.
+pub struct ScreenStream {
+    source: StreamSource,
+}
+
+// `stream_screen_async` reads through tokio; `stream_screen` has no runtime
+// to rely on, so a thread reads the pipe and hands chunks over a channel.
+enum StreamSource {
+    Tokio {
+        _child: Box<AsyncChild>,
+        frames: ReaderStream<ChildStdout>,
+    },
+    Thread {
+        child: Child,
+        chunks: mpsc::UnboundedReceiver<Result<Bytes, io::Error>>,
+    },
+}
+
+impl Stream for ScreenStream {
+    type Item = Result<Bytes, io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match &mut self.source {
+            StreamSource::Tokio { frames, .. } => Pin::new(frames).poll_next(cx),
+            StreamSource::Thread { chunks, .. } => Pin::new(chunks).poll_next(cx),
+        }
+    }
+}
+
+impl Drop for ScreenStream {
+    fn drop(&mut self) {
+        if let StreamSource::Thread { child, .. } = &mut self.source {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeepRecording {
+    #[default]
+    Always,
+    // Only keep the video when the closure panics, e.g. a failed assertion,
+    // or returns `Err` under `try_with_screen_recording`.
+    OnFailure,
+}
+
+fn report_failure(options: &ScreenRecordOptions, error: &io::Error) {
+    if let Some(on_progress) = &options.on_progress {
+        on_progress(ScreenRecordProgress::Failed(error.to_string()));
+    }
+}
+
+// The video from a scoped recording, returned next to the body's value so a
+// recording problem never costs the caller that value. `None` when
+// `KeepRecording::OnFailure` discarded a passing run, hence no bare path;
+// `Err` when stopping, pulling or discarding the recording failed.
+pub type ScopedVideo = Result<Option<PathBuf>, io::Error>;
+
+#[derive(Debug, Clone, Default)]
+pub struct ScopedRecordingOptions {
+    pub record: ScreenRecordOptions,
+    pub local_path: PathBuf,
+    pub keep: KeepRecording,
+}
+
+impl ADB {
+    // Records the screen while `body` runs. The outer error only means the
+    // recording could not start and `body` never ran. When `body` panics the
+    // video is still pulled before the panic is resumed, so the failing run
+    // is kept; its path, or the error that kept it from being saved, arrives
+    // through `on_progress` as `Completed` or `Failed`.
+    pub fn with_screen_recording<T, F>(
+        &self,
+        device: &str,
+        options: &ScopedRecordingOptions,
+        body: F,
+    ) -> Result<(T, ScopedVideo), io::Error>
+    where
+        F: FnOnce() -> T,
+    {
+        self.record_scoped(device, options, body, |_| false)
+    }
+
+    // Like `with_screen_recording`, but an `Err` from `body` also counts as
+    // a failure for `KeepRecording::OnFailure`.
+    pub fn try_with_screen_recording<T, E, F>(
+        &self,
+        device: &str,
+        options: &ScopedRecordingOptions,
+        body: F,
+    ) -> Result<(Result<T, E>, ScopedVideo), io::Error>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        self.record_scoped(device, options, body, Result::is_err)
+    }
+
+    fn record_scoped<T, F>(
+        &self,
+        device: &str,
+        options: &ScopedRecordingOptions,
+        body: F,
+        failed: impl Fn(&T) -> bool,
+    ) -> Result<(T, ScopedVideo), io::Error>
+    where
+        F: FnOnce() -> T,
+    {
+        let handle = self.start_screen_record_with_options(device, &options.record, &options.local_path)?;
+        match panic::catch_unwind(AssertUnwindSafe(body)) {
+            Ok(value) if options.keep == KeepRecording::OnFailure && !failed(&value) => {
+                let video = handle.discard().map(|_| None);
+                Ok((value, video))
+            }
+            Ok(value) => Ok((value, handle.stop().map(Some))),
+            Err(payload) => {
+                if let Err(error) = handle.stop() {
+                    report_failure(&options.record, &error);
+                }
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    pub async fn with_screen_recording_async<T, F, Fut>(
+        &self,
+        device: &str,
+        options: &ScopedRecordingOptions,
+        body: F,
+    ) -> Result<(T, ScopedVideo), io::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.record_scoped_async(device, options, body, |_| false).await
+    }
+
+    pub async fn try_with_screen_recording_async<T, E, F, Fut>(
+        &self,
+        device: &str,
+        options: &ScopedRecordingOptions,
+        body: F,
+    ) -> Result<(Result<T, E>, ScopedVideo), io::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        self.record_scoped_async(device, options, body, Result::is_err).await
+    }
+
+    async fn record_scoped_async<T, F, Fut>(
+        &self,
+        device: &str,
+        options: &ScopedRecordingOptions,
+        body: F,
+        failed: impl Fn(&T) -> bool,
+    ) -> Result<(T, ScopedVideo), io::Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let handle = self.start_screen_record_with_options(device, &options.record, &options.local_path)?;
+        match AssertUnwindSafe(body()).catch_unwind().await {
+            Ok(value) if options.keep == KeepRecording::OnFailure && !failed(&value) => {
+                let video = handle.discard_async().await.map(|_| None);
+                Ok((value, video))
+            }
+            Ok(value) => Ok((value, handle.stop_async().await.map(Some))),
+            Err(payload) => {
+                if let Err(error) = handle.stop_async().await {
+                    report_failure(&options.record, &error);
+                }
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+
+    pub fn start_screen_record_with_options(
+        &self,
+        device: &str,
+        options: &ScreenRecordOptions,
+        local_path: impl AsRef<Path>,
+    ) -> Result<ScreenRecordHandle<'_>, io::Error> {
+        let remote_path = options.remote_path();
+        let child = self.spawn_shell(&format!("{} shell {}", device, options.to_command(&remote_path)))?;
+        let handle = ScreenRecordHandle {
+            adb: self,
+            device: device.to_string(),
+            remote_path,
+            local_path: local_path.as_ref().to_path_buf(),
+            child: Some(child),
+            on_progress: options.on_progress.clone(),
+        };
+        handle.report(ScreenRecordProgress::Started);
+        Ok(handle)
+    }
+
+    // Works without a tokio runtime; the stream can be polled from any
+    // executor, e.g. `futures::executor::block_on_stream`.
+    pub fn stream_screen(&self, device: &str, options: &ScreenRecordOptions) -> Result<ScreenStream, io::Error> {
+        let mut child = Command::new(&self.bin)
+            .arg(stream_command(device, options))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let Some(mut stdout) = child.stdout.take() else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::other("Failed to capture stdout"));
+        };
+        let (sender, chunks) = mpsc::unbounded();
+        thread::spawn(move || {
+            let mut buf = vec![0; STREAM_CHUNK];
+            loop {
+                let chunk = match stdout.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(read) => Ok(Bytes::copy_from_slice(&buf[..read])),
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(error) => Err(error),
+                };
+                let failed = chunk.is_err();
+                if sender.unbounded_send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(ScreenStream {
+            source: StreamSource::Thread { child, chunks },
+        })
+    }
+
+    pub async fn stream_screen_async(&self, device: &str, options: &ScreenRecordOptions) -> Result<ScreenStream, io::Error> {
+        let mut child = AsyncCommand::new(&self.bin)
+            .arg(stream_command(device, options))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("Failed to capture stdout"))?;
+        Ok(ScreenStream {
+            source: StreamSource::Tokio {
+                _child: Box::new(child),
+                frames: ReaderStream::new(stdout),
+            },
+        })
+    }
+}
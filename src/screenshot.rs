@@ -0,0 +1,255 @@
+// src/screenshot.rs
+
+use crate::ADB;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    // UI hierarchy dumps describe bounds as `[left,top][right,bottom]`.
+    pub fn from_bounds(left: u32, top: u32, right: u32, bottom: u32) -> Self {
+        Rect {
+            x: left,
+            y: top,
+            width: right.saturating_sub(left),
+            height: bottom.saturating_sub(top),
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && self.x.checked_add(self.width).is_none_or(|right| x < right)
+            && self.y.checked_add(self.height).is_none_or(|bottom| y < bottom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScreenshotOptions {
+    pub region: Option<Rect>,
+    pub scale_to_width: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComparisonOptions {
+    // Fraction of compared pixels allowed to differ, 0.0..=1.0.
+    pub threshold: f64,
+    // Maximum per-channel difference for two pixels to count as equal.
+    pub pixel_tolerance: u8,
+    pub ignore_regions: Vec<Rect>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffResult {
+    pub matches: bool,
+    pub size_mismatch: bool,
+    pub differing_pixels: u64,
+    pub compared_pixels: u64,
+    pub diff_ratio: f64,
+    // PNG with differing pixels in red over a dimmed copy of `actual`.
+    // Empty when the image sizes don't match.
+    pub diff_image: Vec<u8>,
+}
+
+fn decode_png(png: &[u8]) -> Result<DynamicImage, io::Error> {
+    image::load_from_memory_with_format(png, ImageFormat::Png)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, io::Error> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(io::Error::other)?;
+    Ok(png)
+}
+
+fn crop_and_scale(png: &[u8], options: &ScreenshotOptions) -> Result<Vec<u8>, io::Error> {
+    if options.region.is_none() && options.scale_to_width.is_none() {
+        return Ok(png.to_vec());
+    }
+    let mut image = decode_png(png)?;
+
+    if let Some(region) = options.region {
+        if region.width == 0
+            || region.height == 0
+            || region.x.checked_add(region.width).is_none_or(|right| right > image.width())
+            || region.y.checked_add(region.height).is_none_or(|bottom| bottom > image.height())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Region {:?} is outside the {}x{} screenshot",
+                    region,
+                    image.width(),
+                    image.height()
+                ),
+            ));
+        }
+        image = image.crop_imm(region.x, region.y, region.width, region.height);
+    }
+
+    if let Some(width) = options.scale_to_width.filter(|&width| width > 0 && width != image.width()) {
+        let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+        image = image.resize_exact(width, height, FilterType::Triangle);
+    }
+
+    encode_png(&image)
+}
+
+fn channels_close(a: &Rgba<u8>, b: &Rgba<u8>, tolerance: u8) -> bool {
+    a.0.iter().zip(b.0.iter()).all(|(x, y)| x.abs_diff(*y) <= tolerance)
+}
+
+pub fn compare_screenshots(actual: &[u8], golden: &[u8], options: &ComparisonOptions) -> Result<DiffResult, io::Error> {
+    let actual = decode_png(actual)?.to_rgba8();
+    let golden = decode_png(golden)?.to_rgba8();
+
+    if actual.dimensions() != golden.dimensions() {
+        return Ok(DiffResult {
+            matches: false,
+            size_mismatch: true,
+            differing_pixels: 0,
+            compared_pixels: 0,
+            diff_ratio: 1.0,
+            diff_image: Vec::new(),
+        });
+    }
+
+    let (width, height) = actual.dimensions();
+    let mut diff = RgbaImage::new(width, height);
+    let mut differing_pixels = 0u64;
+    let mut compared_pixels = 0u64;
+
+    for (x, y, pixel) in actual.enumerate_pixels() {
+        if options.ignore_regions.iter().any(|region| region.contains(x, y)) {
+            diff.put_pixel(x, y, Rgba([0, 0, 96, 255]));
+            continue;
+        }
+        compared_pixels += 1;
+        if channels_close(pixel, golden.get_pixel(x, y), options.pixel_tolerance) {
+            let [r, g, b, _] = pixel.0;
+            // Grayscale at a third of the brightness so red stands out.
+            let luma = ((r as u32 * 3 + g as u32 * 6 + b as u32) / 30) as u8;
+            diff.put_pixel(x, y, Rgba([luma, luma, luma, 255]));
+        } else {
+            differing_pixels += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        }
+    }
+
+    let diff_ratio = if compared_pixels == 0 {
+        0.0
+    } else {
+        differing_pixels as f64 / compared_pixels as f64
+    };
+
+    Ok(DiffResult {
+        matches: diff_ratio <= options.threshold,
+        size_mismatch: false,
+        differing_pixels,
+        compared_pixels,
+        diff_ratio,
+        diff_image: encode_png(&DynamicImage::ImageRgba8(diff))?,
+    })
+}
+
+fn sibling_path(golden_path: &Path, suffix: &str) -> PathBuf {
+    let stem = golden_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    golden_path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+// On mismatch the actual screenshot and the diff image are written next to
+// the golden file as `<name>.actual.png` and `<name>.diff.png`.
+fn check_against_golden(actual: &[u8], golden_path: &Path, options: &ComparisonOptions) -> Result<DiffResult, io::Error> {
+    let golden = fs::read(golden_path)?;
+    let result = compare_screenshots(actual, &golden, options)?;
+    if result.matches {
+        return Ok(result);
+    }
+
+    fs::write(sibling_path(golden_path, "actual"), actual)?;
+    if !result.diff_image.is_empty() {
+        fs::write(sibling_path(golden_path, "diff"), &result.diff_image)?;
+    }
+    let reason = if result.size_mismatch {
+        "size differs".to_string()
+    } else {
+        format!("{:.4}% of pixels differ", result.diff_ratio * 100.0)
+    };
+    Err(io::Error::other(format!(
+        "Screen does not match {}: {}",
+        golden_path.display(),
+        reason
+    )))
+}
+
+impl ADB {
+    pub fn get_screenshot_with_options(&self, device: &str, options: &ScreenshotOptions) -> Result<Vec<u8>, io::Error> {
+        let png = self.get_screenshot_png(device)?;
+        crop_and_scale(&png, options)
+    }
+
+    pub async fn get_screenshot_with_options_async(&self, device: &str, options: &ScreenshotOptions) -> Result<Vec<u8>, io::Error> {
+        let png = self.get_screenshot_png_async(device).await?;
+        crop_and_scale(&png, options)
+    }
+
+    pub fn get_screenshot_region(&self, device: &str, region: Rect) -> Result<Vec<u8>, io::Error> {
+        self.get_screenshot_with_options(
+            device,
+            &ScreenshotOptions {
+                region: Some(region),
+                scale_to_width: None,
+            },
+        )
+    }
+
+    pub async fn get_screenshot_region_async(&self, device: &str, region: Rect) -> Result<Vec<u8>, io::Error> {
+        self.get_screenshot_with_options_async(
+            device,
+            &ScreenshotOptions {
+                region: Some(region),
+                scale_to_width: None,
+            },
+        )
+        .await
+    }
+
+    pub fn assert_screen_matches(
+        &self,
+        device: &str,
+        golden_path: impl AsRef<Path>,
+        options: &ComparisonOptions,
+    ) -> Result<DiffResult, io::Error> {
+        let actual = self.get_screenshot_png(device)?;
+        check_against_golden(&actual, golden_path.as_ref(), options)
+    }
+
+    pub async fn assert_screen_matches_async(
+        &self,
+        device: &str,
+        golden_path: impl AsRef<Path>,
+        options: &ComparisonOptions,
+    ) -> Result<DiffResult, io::Error> {
+        let actual = self.get_screenshot_png_async(device).await?;
+        check_against_golden(&actual, golden_path.as_ref(), options)
+    }
+}
@@ -0,0 +1,175 @@
+// src/storage.rs
+
+use crate::ADB;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeKind {
+    Internal,
+    Emulated,
+    Public,
+    Adopted,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageVolume {
+    pub id: Option<String>,
+    pub kind: VolumeKind,
+    pub state: String,
+    pub filesystem: Option<String>,
+    pub mount_point: Option<String>,
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+}
+
+impl StorageVolume {
+    pub fn is_mounted(&self) -> bool {
+        self.state == "mounted"
+    }
+}
+
+struct DfRow {
+    filesystem: String,
+    total_bytes: u64,
+    used_bytes: u64,
+    available_bytes: u64,
+    mount_point: String,
+}
+
+// `df -k` rows: `Filesystem 1K-blocks Used Available Use% Mounted on`.
+fn parse_df(output: &str) -> Vec<DfRow> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            let kb = |index: usize| fields[index].parse::<u64>().ok().map(|value| value * 1024);
+            Some(DfRow {
+                filesystem: fields[0].to_string(),
+                total_bytes: kb(1)?,
+                used_bytes: kb(2)?,
+                available_bytes: kb(3)?,
+                mount_point: fields[5..].join(" "),
+            })
+        })
+        .collect()
+}
+
+struct SmVolume {
+    id: String,
+    state: String,
+    fs_uuid: Option<String>,
+}
+
+// `sm list-volumes all` rows: `<id> <state> <fsUuid|null>`.
+fn parse_sm(output: &str) -> Vec<SmVolume> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some(SmVolume {
+                id: fields.next()?.to_string(),
+                state: fields.next()?.to_string(),
+                fs_uuid: fields.next().filter(|uuid| *uuid != "null").map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+fn classify_mount(mount_point: &str) -> Option<VolumeKind> {
+    if mount_point == "/data" {
+        Some(VolumeKind::Internal)
+    } else if mount_point.starts_with("/storage/emulated") {
+        Some(VolumeKind::Emulated)
+    } else if mount_point.starts_with("/mnt/expand/") {
+        Some(VolumeKind::Adopted)
+    } else if mount_point.starts_with("/storage/") || mount_point.starts_with("/mnt/media_rw/") {
+        Some(VolumeKind::Public)
+    } else {
+        None
+    }
+}
+
+// Where vold mounts each volume type, used to match `sm` entries with `df`.
+fn expected_mount(volume: &SmVolume) -> (VolumeKind, Option<String>) {
+    let uuid = volume.fs_uuid.as_deref();
+    if volume.id == "private" {
+        (VolumeKind::Internal, Some("/data".to_string()))
+    } else if volume.id.starts_with("emulated") {
+        (VolumeKind::Emulated, Some("/storage/emulated".to_string()))
+    } else if volume.id.starts_with("public:") {
+        (VolumeKind::Public, uuid.map(|uuid| format!("/storage/{}", uuid)))
+    } else if volume.id.starts_with("private:") {
+        (VolumeKind::Adopted, uuid.map(|uuid| format!("/mnt/expand/{}", uuid)))
+    } else {
+        (VolumeKind::Other, None)
+    }
+}
+
+fn merge_volumes(df: &str, sm: &str) -> Vec<StorageVolume> {
+    let mut rows: Vec<(VolumeKind, DfRow)> = parse_df(df)
+        .into_iter()
+        .filter_map(|row| classify_mount(&row.mount_point).map(|kind| (kind, row)))
+        .collect();
+    let mut volumes = Vec::new();
+
+    for volume in parse_sm(sm) {
+        let (kind, mount) = expected_mount(&volume);
+        let row = mount.as_deref().and_then(|mount| {
+            let index = rows
+                .iter()
+                .position(|(_, row)| row.mount_point == mount || row.mount_point.starts_with(&format!("{}/", mount)))?;
+            Some(rows.remove(index).1)
+        });
+        volumes.push(StorageVolume {
+            id: Some(volume.id),
+            kind,
+            state: volume.state,
+            filesystem: row.as_ref().map(|row| row.filesystem.clone()),
+            mount_point: row.as_ref().map(|row| row.mount_point.clone()).or(mount),
+            total_bytes: row.as_ref().map_or(0, |row| row.total_bytes),
+            used_bytes: row.as_ref().map_or(0, |row| row.used_bytes),
+            available_bytes: row.as_ref().map_or(0, |row| row.available_bytes),
+        });
+    }
+
+    // Anything df reports that vold didn't list, e.g. on devices without `sm`.
+    for (kind, row) in rows {
+        volumes.push(StorageVolume {
+            id: None,
+            kind,
+            state: "mounted".to_string(),
+            filesystem: Some(row.filesystem),
+            mount_point: Some(row.mount_point),
+            total_bytes: row.total_bytes,
+            used_bytes: row.used_bytes,
+            available_bytes: row.available_bytes,
+        });
+    }
+    volumes
+}
+
+impl ADB {
+    pub fn get_storage_volumes(&self, device: &str) -> Result<Vec<StorageVolume>, io::Error> {
+        let df = self.run_adb(&format!("{} shell df -k", device))?;
+        // `sm` is missing before Android 6 and restricted on some builds.
+        let sm = self
+            .run_adb(&format!("{} shell sm list-volumes all", device))
+            .unwrap_or_default();
+        Ok(merge_volumes(&df, &sm))
+    }
+
+    pub async fn get_storage_volumes_async(&self, device: &str) -> Result<Vec<StorageVolume>, io::Error> {
+        let df = self.run_adb_async(&format!("{} shell df -k", device)).await?;
+        let sm = self
+            .run_adb_async(&format!("{} shell sm list-volumes all", device))
+            .await
+            .unwrap_or_default();
+        Ok(merge_volumes(&df, &sm))
+    }
+}
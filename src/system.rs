@@ -0,0 +1,365 @@
+// src/system.rs
+
+use crate::ADB;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const BOOT_POLL_INITIAL: Duration = Duration::from_millis(250);
+const BOOT_POLL_MAX: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiLevelTooLow {
+    pub required: u32,
+    pub actual: u32,
+}
+
+impl fmt::Display for ApiLevelTooLow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Device API level {} is below the required {}", self.actual, self.required)
+    }
+}
+
+impl Error for ApiLevelTooLow {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemInfo {
+    pub api_level: u32,
+    pub release: String,
+    pub security_patch: Option<String>,
+    pub manufacturer: String,
+    pub model: String,
+    pub abi: String,
+    pub fingerprint: String,
+}
+
+impl SystemInfo {
+    pub fn is_at_least_api(&self, level: u32) -> bool {
+        self.api_level >= level
+    }
+
+    // Wraps `ApiLevelTooLow` in an `io::Error` of kind `Unsupported`; callers
+    // can recover it with `err.get_ref()` and `downcast_ref`.
+    pub fn require_api(&self, level: u32) -> Result<(), io::Error> {
+        if self.is_at_least_api(level) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                ApiLevelTooLow {
+                    required: level,
+                    actual: self.api_level,
+                },
+            ))
+        }
+    }
+
+    // Days between the security patch level and today's host date.
+    pub fn security_patch_age_days(&self) -> Option<i64> {
+        let patch = parse_date(self.security_patch.as_deref()?)?;
+        let today = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() / 86_400;
+        Some(today as i64 - patch)
+    }
+
+    fn from_props(props: &HashMap<String, String>) -> Result<Self, io::Error> {
+        let prop = |name: &str| props.get(name).cloned().unwrap_or_default();
+        let api_level = prop("ro.build.version.sdk").parse().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "Missing or invalid ro.build.version.sdk")
+        })?;
+        Ok(SystemInfo {
+            api_level,
+            release: prop("ro.build.version.release"),
+            security_patch: Some(prop("ro.build.version.security_patch")).filter(|patch| !patch.is_empty()),
+            manufacturer: prop("ro.product.manufacturer"),
+            model: prop("ro.product.model"),
+            abi: prop("ro.product.cpu.abi"),
+            fingerprint: prop("ro.build.fingerprint"),
+        })
+    }
+}
+
+// Days since the Unix epoch for a `YYYY-MM-DD` date (Howard Hinnant's
+// days_from_civil).
+fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+// `getprop` with no arguments prints `[name]: [value]` lines.
+fn parse_props(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, value) = line.trim().split_once("]: [")?;
+            Some((
+                name.strip_prefix('[')?.to_string(),
+                value.strip_suffix(']')?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn parse_uptime(output: &str) -> Result<Duration, io::Error> {
+    output
+        .split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse::<f64>().ok())
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unexpected /proc/uptime output: {}", output.trim()),
+            )
+        })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootReasonEntry {
+    pub reason: String,
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BootInfo {
+    pub uptime: Duration,
+    // Host clock minus uptime, so only as accurate as the adb round-trip.
+    pub booted_at: Option<SystemTime>,
+    pub boot_reason: Option<String>,
+    pub boot_completed: bool,
+    pub boot_completed_secs: Option<i64>,
+    // Most recent first, from persist.sys.boot.reason.history.
+    pub boot_reason_history: Vec<BootReasonEntry>,
+    // `bootstat -p` values; empty when bootstat isn't accessible.
+    pub bootstat: BTreeMap<String, i64>,
+}
+
+impl BootInfo {
+    // Counts boots recorded within `window` of the latest one, which makes
+    // reboot loops stand out.
+    pub fn recent_boot_count(&self, window: Duration) -> usize {
+        let Some(latest) = self.boot_reason_history.iter().map(|entry| entry.timestamp).max() else {
+            return 0;
+        };
+        let since = latest.saturating_sub(window.as_secs());
+        self.boot_reason_history
+            .iter()
+            .filter(|entry| entry.timestamp >= since)
+            .count()
+    }
+}
+
+fn parse_reason_history(history: &str) -> Vec<BootReasonEntry> {
+    history
+        .split(['\n', ' '])
+        .filter_map(|entry| {
+            let (reason, timestamp) = entry.trim().rsplit_once(',')?;
+            Some(BootReasonEntry {
+                reason: reason.to_string(),
+                timestamp: timestamp.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+// `bootstat -p` prints a header followed by `<event> <value>` lines.
+fn parse_bootstat(output: &str) -> BTreeMap<String, i64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let name = fields.next()?;
+            let value = fields.next()?.parse().ok()?;
+            Some((name.to_string(), value))
+        })
+        .collect()
+}
+
+fn boot_timed_out(device: &str, timeout: Duration, pending: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!(
+            "{} did not finish booting within {:?}; last check: {}",
+            device.trim_start_matches("-s ").trim(),
+            timeout,
+            pending
+        ),
+    )
+}
+
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|value| !value.is_empty())
+}
+
+impl ADB {
+    pub fn get_uptime(&self, device: &str) -> Result<Duration, io::Error> {
+        let output = self.run_adb(&format!("{} shell cat /proc/uptime", device))?;
+        parse_uptime(&output)
+    }
+
+    pub async fn get_uptime_async(&self, device: &str) -> Result<Duration, io::Error> {
+        let output = self
+            .run_adb_async(&format!("{} shell cat /proc/uptime", device))
+            .await?;
+        parse_uptime(&output)
+    }
+
+    pub fn get_boot_info(&self, device: &str) -> Result<BootInfo, io::Error> {
+        let uptime = self.get_uptime(device)?;
+        let bootstat = self
+            .run_adb(&format!("{} shell bootstat -p", device))
+            .map(|output| parse_bootstat(&output))
+            .unwrap_or_default();
+        Ok(BootInfo {
+            uptime,
+            booted_at: SystemTime::now().checked_sub(uptime),
+            boot_reason: non_empty(self.get_prop(device, "sys.boot.reason")?)
+                .or(non_empty(self.get_prop(device, "ro.boot.bootreason")?)),
+            boot_completed: self.get_prop(device, "sys.boot_completed")? == "1",
+            boot_completed_secs: bootstat.get("boot_complete").copied(),
+            boot_reason_history: parse_reason_history(&self.get_prop(device, "persist.sys.boot.reason.history")?),
+            bootstat,
+        })
+    }
+
+    pub async fn get_boot_info_async(&self, device: &str) -> Result<BootInfo, io::Error> {
+        let uptime = self.get_uptime_async(device).await?;
+        let bootstat = self
+            .run_adb_async(&format!("{} shell bootstat -p", device))
+            .await
+            .map(|output| parse_bootstat(&output))
+            .unwrap_or_default();
+        Ok(BootInfo {
+            uptime,
+            booted_at: SystemTime::now().checked_sub(uptime),
+            boot_reason: non_empty(self.get_prop_async(device, "sys.boot.reason").await?)
+                .or(non_empty(self.get_prop_async(device, "ro.boot.bootreason").await?)),
+            boot_completed: self.get_prop_async(device, "sys.boot_completed").await? == "1",
+            boot_completed_secs: bootstat.get("boot_complete").copied(),
+            boot_reason_history: parse_reason_history(
+                &self
+                    .get_prop_async(device, "persist.sys.boot.reason.history")
+                    .await?,
+            ),
+            bootstat,
+        })
+    }
+
+    // What a booting device is still missing, or None once it is ready.
+    // The props flip before services are up, so the package manager has to
+    // answer too before installs stop failing. Every probe is cut off at
+    // `deadline`, since a half-started emulator can leave `adb shell` hanging.
+    fn boot_pending(&self, device: &str, deadline: Instant) -> Result<Option<&'static str>, io::Error> {
+        let probe = |command: &str| self.run_adb_until(&format!("{} shell {}", device, command), deadline);
+        if probe("getprop sys.boot_completed")?.trim() != "1" {
+            return Ok(Some("sys.boot_completed is not set"));
+        }
+        if probe("getprop dev.bootcomplete")?.trim() != "1" {
+            return Ok(Some("dev.bootcomplete is not set"));
+        }
+        if !probe("pm path android")?.contains("package:") {
+            return Ok(Some("package manager is not ready"));
+        }
+        Ok(None)
+    }
+
+    async fn boot_pending_async(&self, device: &str, deadline: Instant) -> Result<Option<&'static str>, io::Error> {
+        let probe = |command: &'static str| async move {
+            let command = format!("{} shell {}", device, command);
+            tokio::time::timeout_at(deadline.into(), self.run_adb_async(&command))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, format!("Command timed out: {}", command)))?
+        };
+        if probe("getprop sys.boot_completed").await?.trim() != "1" {
+            return Ok(Some("sys.boot_completed is not set"));
+        }
+        if probe("getprop dev.bootcomplete").await?.trim() != "1" {
+            return Ok(Some("dev.bootcomplete is not set"));
+        }
+        if !probe("pm path android").await?.contains("package:") {
+            return Ok(Some("package manager is not ready"));
+        }
+        Ok(None)
+    }
+
+    // Polls with exponential backoff until the device has booted far enough
+    // to install apps. adb errors count as not booted yet, since a starting
+    // emulator shows up offline or not at all for a while.
+    pub fn wait_for_boot_complete(&self, device: &str, timeout: Duration) -> Result<(), io::Error> {
+        let start = Instant::now();
+        let mut delay = BOOT_POLL_INITIAL;
+        loop {
+            let pending = match self.boot_pending(device, start + timeout) {
+                Ok(None) => return Ok(()),
+                Ok(Some(pending)) => pending.to_string(),
+                Err(error) => error.to_string().trim().to_string(),
+            };
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(boot_timed_out(device, timeout, &pending));
+            }
+            std::thread::sleep(delay.min(timeout - elapsed));
+            delay = (delay * 2).min(BOOT_POLL_MAX);
+        }
+    }
+
+    pub async fn wait_for_boot_complete_async(&self, device: &str, timeout: Duration) -> Result<(), io::Error> {
+        let start = Instant::now();
+        let mut delay = BOOT_POLL_INITIAL;
+        loop {
+            let pending = match self.boot_pending_async(device, start + timeout).await {
+                Ok(None) => return Ok(()),
+                Ok(Some(pending)) => pending.to_string(),
+                Err(error) => error.to_string().trim().to_string(),
+            };
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(boot_timed_out(device, timeout, &pending));
+            }
+            tokio::time::sleep(delay.min(timeout - elapsed)).await;
+            delay = (delay * 2).min(BOOT_POLL_MAX);
+        }
+    }
+
+    pub fn get_props(&self, device: &str) -> Result<HashMap<String, String>, io::Error> {
+        let output = self.run_adb(&format!("{} shell getprop", device))?;
+        Ok(parse_props(&output))
+    }
+
+    pub async fn get_props_async(&self, device: &str) -> Result<HashMap<String, String>, io::Error> {
+        let output = self.run_adb_async(&format!("{} shell getprop", device)).await?;
+        Ok(parse_props(&output))
+    }
+
+    pub fn get_system_info(&self, device: &str) -> Result<SystemInfo, io::Error> {
+        SystemInfo::from_props(&self.get_props(device)?)
+    }
+
+    pub async fn get_system_info_async(&self, device: &str) -> Result<SystemInfo, io::Error> {
+        SystemInfo::from_props(&self.get_props_async(device).await?)
+    }
+
+    pub fn require_api(&self, device: &str, level: u32) -> Result<SystemInfo, io::Error> {
+        let info = self.get_system_info(device)?;
+        info.require_api(level)?;
+        Ok(info)
+    }
+
+    pub async fn require_api_async(&self, device: &str, level: u32) -> Result<SystemInfo, io::Error> {
+        let info = self.get_system_info_async(device).await?;
+        info.require_api(level)?;
+        Ok(info)
+    }
+}
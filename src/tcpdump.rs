@@ -0,0 +1,204 @@
+// src/tcpdump.rs
+
+use crate::{shell_quote, ADB};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const BUNDLED_TCPDUMP: &str = "/data/local/tmp/tcpdump";
+const FINALIZE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone)]
+pub struct TcpdumpOptions {
+    // Captures on all interfaces (`any`) when unset.
+    pub iface: Option<String>,
+    // A pcap filter expression such as `tcp port 443`.
+    pub filter: Option<String>,
+    pub duration: Duration,
+    // Stops the capture early once the pcap reaches this many bytes.
+    pub max_size: Option<u64>,
+}
+
+impl Default for TcpdumpOptions {
+    fn default() -> Self {
+        TcpdumpOptions {
+            iface: None,
+            filter: None,
+            duration: Duration::from_secs(30),
+            max_size: None,
+        }
+    }
+}
+
+impl TcpdumpOptions {
+    // `-U` flushes every packet so the size limit can be checked while the
+    // capture is running.
+    fn to_command(&self, binary: &str, output: &str) -> String {
+        let mut args = vec![
+            binary.to_string(),
+            format!("-i {}", shell_quote(self.iface.as_deref().unwrap_or("any"))),
+            "-U".to_string(),
+            format!("-w {}", output),
+        ];
+        if let Some(filter) = &self.filter {
+            args.push(shell_quote(filter));
+        }
+        args.join(" ")
+    }
+}
+
+// How tcpdump gets launched: the bundled binary in /data/local/tmp wins over
+// the system one, and commands go through `su` unless adbd already runs as
+// root.
+struct Capture {
+    device: String,
+    binary: &'static str,
+    as_root: bool,
+    remote_path: String,
+}
+
+impl Capture {
+    fn new(device: &str, bundled: bool, uid: &str) -> Self {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        Capture {
+            device: device.to_string(),
+            binary: if bundled { BUNDLED_TCPDUMP } else { "tcpdump" },
+            as_root: uid.trim() == "0",
+            remote_path: format!("/data/local/tmp/rust-adb-capture-{}.pcap", stamp),
+        }
+    }
+
+    fn shell(&self, command: &str) -> String {
+        if self.as_root {
+            format!("{} shell {}", self.device, command)
+        } else {
+            format!("{} shell su -c {}", self.device, shell_quote(command))
+        }
+    }
+
+    fn start_command(&self, options: &TcpdumpOptions) -> String {
+        self.shell(&options.to_command(self.binary, &self.remote_path))
+    }
+
+    fn size_command(&self) -> String {
+        self.shell(&format!("stat -c %s {}", self.remote_path))
+    }
+
+    fn interrupt_command(&self) -> String {
+        self.shell(&format!("pkill -INT -f {}", self.remote_path))
+    }
+
+    fn cleanup_command(&self) -> String {
+        self.shell(&format!("rm -f {}", self.remote_path))
+    }
+
+    fn limit_reached(&self, options: &TcpdumpOptions, start: Instant, size: Option<u64>) -> bool {
+        start.elapsed() >= options.duration || matches!((options.max_size, size), (Some(max), Some(size)) if size >= max)
+    }
+
+    fn try_finished(child: &mut Child, start: Instant) -> Result<bool, io::Error> {
+        if child.try_wait()?.is_some() {
+            return Ok(true);
+        }
+        if start.elapsed() >= FINALIZE_TIMEOUT {
+            child.kill()?;
+            child.wait()?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+fn no_capture(error: io::Error) -> io::Error {
+    io::Error::other(format!(
+        "tcpdump produced no capture (is the device rooted or {} present?): {}",
+        BUNDLED_TCPDUMP, error
+    ))
+}
+
+impl ADB {
+    fn prepare_capture(&self, device: &str) -> Result<Capture, io::Error> {
+        let bundled = self.run_adb(&format!("{} shell ls {}", device, BUNDLED_TCPDUMP)).is_ok();
+        let uid = self.run_adb(&format!("{} shell id -u", device))?;
+        Ok(Capture::new(device, bundled, &uid))
+    }
+
+    async fn prepare_capture_async(&self, device: &str) -> Result<Capture, io::Error> {
+        let bundled = self
+            .run_adb_async(&format!("{} shell ls {}", device, BUNDLED_TCPDUMP))
+            .await
+            .is_ok();
+        let uid = self.run_adb_async(&format!("{} shell id -u", device)).await?;
+        Ok(Capture::new(device, bundled, &uid))
+    }
+
+    // Runs tcpdump until the duration or size limit is hit, interrupts it so
+    // the pcap is flushed, then moves the file to `local_path`.
+    pub fn capture_packets(&self, device: &str, options: &TcpdumpOptions, local_path: impl AsRef<Path>) -> Result<PathBuf, io::Error> {
+        let capture = self.prepare_capture(device)?;
+        let mut child = self.spawn_shell(&capture.start_command(options))?;
+
+        let start = Instant::now();
+        while child.try_wait()?.is_none() {
+            let size = match options.max_size {
+                Some(_) => self.run_adb(&capture.size_command()).ok().and_then(|size| size.trim().parse().ok()),
+                None => None,
+            };
+            if capture.limit_reached(options, start, size) {
+                // pkill fails when tcpdump already exited on its own.
+                let _ = self.run_adb(&capture.interrupt_command());
+                break;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        let start = Instant::now();
+        while !Capture::try_finished(&mut child, start)? {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        let local = local_path.as_ref().to_path_buf();
+        let pulled = self.pull(device, &capture.remote_path, &local.to_string_lossy());
+        let _ = self.run_adb(&capture.cleanup_command());
+        pulled.map_err(no_capture)?;
+        Ok(local)
+    }
+
+    pub async fn capture_packets_async(&self, device: &str, options: &TcpdumpOptions, local_path: impl AsRef<Path>) -> Result<PathBuf, io::Error> {
+        let capture = self.prepare_capture_async(device).await?;
+        let mut child = self.spawn_shell(&capture.start_command(options))?;
+
+        let start = Instant::now();
+        while child.try_wait()?.is_none() {
+            let size = match options.max_size {
+                Some(_) => self
+                    .run_adb_async(&capture.size_command())
+                    .await
+                    .ok()
+                    .and_then(|size| size.trim().parse().ok()),
+                None => None,
+            };
+            if capture.limit_reached(options, start, size) {
+                let _ = self.run_adb_async(&capture.interrupt_command()).await;
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let start = Instant::now();
+        while !Capture::try_finished(&mut child, start)? {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+
+        let local = local_path.as_ref().to_path_buf();
+        let pulled = self
+            .pull_async(device, &capture.remote_path, &local.to_string_lossy())
+            .await;
+        let _ = self.run_adb_async(&capture.cleanup_command()).await;
+        pulled.map_err(no_capture)?;
+        Ok(local)
+    }
+}
@@ -0,0 +1,110 @@
+// src/telephony.rs
+
+use crate::ADB;
+use std::io;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataState {
+    #[default]
+    Unknown,
+    Disconnected,
+    Connecting,
+    Connected,
+    Suspended,
+}
+
+impl DataState {
+    // Values from android.telephony.TelephonyManager.DATA_*.
+    pub(crate) fn from_code(code: i32) -> Self {
+        match code {
+            0 => DataState::Disconnected,
+            1 => DataState::Connecting,
+            2 => DataState::Connected,
+            3 => DataState::Suspended,
+            _ => DataState::Unknown,
+        }
+    }
+}
+
+// Deliberately limited to network-level information: device identifiers
+// such as the IMEI or subscriber ID are never read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TelephonyInfo {
+    pub operator_name: Option<String>,
+    pub sim_operator_name: Option<String>,
+    pub network_type: Option<String>,
+    pub sim_state: Option<String>,
+    pub data_state: DataState,
+    // 0 (none) to 4 (great), as shown in the status bar.
+    pub signal_level: Option<u8>,
+}
+
+impl TelephonyInfo {
+    pub fn has_sim(&self) -> bool {
+        self.sim_state.as_deref() == Some("READY") || self.sim_state.as_deref() == Some("LOADED")
+    }
+
+    // Props hold one comma-separated value per SIM slot; registry entries are
+    // listed per phone id. Only the first slot is reported.
+    fn parse(registry: &str, operator: &str, sim_operator: &str, network_type: &str, sim_state: &str) -> Self {
+        let first_slot = |value: &str| {
+            value
+                .split(',')
+                .next()
+                .map(str::trim)
+                .filter(|value| !value.is_empty() && *value != "Unknown")
+                .map(str::to_string)
+        };
+
+        let mut info = TelephonyInfo {
+            operator_name: first_slot(operator),
+            sim_operator_name: first_slot(sim_operator),
+            network_type: first_slot(network_type),
+            sim_state: first_slot(sim_state),
+            ..TelephonyInfo::default()
+        };
+
+        for line in registry.lines().map(str::trim) {
+            if let Some(code) = line.strip_prefix("mDataConnectionState=") {
+                if info.data_state == DataState::Unknown {
+                    info.data_state = DataState::from_code(code.trim().parse().unwrap_or(-1));
+                }
+            } else if let Some(strength) = line.strip_prefix("mSignalStrength=") {
+                if info.signal_level.is_none() {
+                    info.signal_level = strength
+                        .split("level=")
+                        .skip(1)
+                        .filter_map(|rest| rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok())
+                        .max();
+                }
+            }
+        }
+        info
+    }
+}
+
+impl ADB {
+    pub fn get_telephony_info(&self, device: &str) -> Result<TelephonyInfo, io::Error> {
+        let registry = self.run_adb(&format!("{} shell dumpsys telephony.registry", device))?;
+        Ok(TelephonyInfo::parse(
+            &registry,
+            &self.get_prop(device, "gsm.operator.alpha")?,
+            &self.get_prop(device, "gsm.sim.operator.alpha")?,
+            &self.get_prop(device, "gsm.network.type")?,
+            &self.get_prop(device, "gsm.sim.state")?,
+        ))
+    }
+
+    pub async fn get_telephony_info_async(&self, device: &str) -> Result<TelephonyInfo, io::Error> {
+        let registry = self
+            .run_adb_async(&format!("{} shell dumpsys telephony.registry", device))
+            .await?;
+        Ok(TelephonyInfo::parse(
+            &registry,
+            &self.get_prop_async(device, "gsm.operator.alpha").await?,
+            &self.get_prop_async(device, "gsm.sim.operator.alpha").await?,
+            &self.get_prop_async(device, "gsm.network.type").await?,
+            &self.get_prop_async(device, "gsm.sim.state").await?,
+        ))
+    }
+}
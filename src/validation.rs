@@ -0,0 +1,312 @@
+// src/validation.rs
+
+use crate::automation::substitute;
+use crate::{LoopItems, StepAction, Workflow, WorkflowStep, ADB};
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationIssue {
+    // Named like step results (`<loop>.<step>` inside loops); None for
+    // problems with the workflow as a whole.
+    pub step: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.step {
+            Some(step) => write!(f, "{}: {}", step, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub workflow: String,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+// Walks the steps the way a run would, tracking which step names and loop
+// variables are in scope at each point.
+struct Validator<'w> {
+    workflow: &'w Workflow,
+    issues: Vec<ValidationIssue>,
+    // Steps that have run by the time the current step starts, innermost
+    // scope last.
+    known: Vec<String>,
+    loop_vars: Vec<String>,
+    // Custom step types that have a handler.
+    step_types: &'w [&'w str],
+}
+
+impl<'w> Validator<'w> {
+    fn issue(&mut self, step: Option<&str>, message: String) {
+        self.issues.push(ValidationIssue {
+            step: step.map(str::to_string),
+            message,
+        });
+    }
+
+    // `loop[0].step` style names refer into a loop that already finished.
+    fn is_known(&self, step: &str) -> bool {
+        let base = step.split_once('[').map_or(step, |(base, _)| base);
+        self.known.iter().any(|known| known == step || known == base)
+    }
+
+    fn check_text(&mut self, step: &str, text: &str) {
+        let names = RefCell::new(Vec::new());
+        let parsed = substitute(text, |name| {
+            names.borrow_mut().push(name.to_string());
+            Some(String::new())
+        });
+        if let Err(error) = parsed {
+            self.issue(Some(step), error.to_string());
+        }
+        for name in names.into_inner() {
+            if name == "device" || self.loop_vars.contains(&name) || self.workflow.vars.contains_key(&name) {
+                continue;
+            }
+            if let Some(variable) = name.strip_prefix("env.") {
+                if std::env::var(variable).is_err() {
+                    self.issue(Some(step), format!("Environment variable {} is not set", variable));
+                }
+                continue;
+            }
+            if let Some(reference) = name.strip_prefix("steps.") {
+                match reference.rsplit_once('.') {
+                    Some((_, field)) if field != "output" && field != "status" => {
+                        self.issue(Some(step), format!("${{{}}}: steps expose only output and status", name))
+                    }
+                    Some((target, _)) if !self.is_known(target) => {
+                        self.issue(Some(step), format!("${{{}}} refers to step {:?}, which has not run by then", name, target))
+                    }
+                    Some(_) => {}
+                    None => self.issue(Some(step), format!("${{{}}} is missing .output or .status", name)),
+                }
+                continue;
+            }
+            self.issue(Some(step), format!("Undefined variable ${{{}}}", name));
+        }
+    }
+
+    fn check_value(&mut self, step: &str, value: &Value) {
+        match value {
+            Value::String(text) => self.check_text(step, text),
+            Value::Array(items) => items.iter().for_each(|item| self.check_value(step, item)),
+            Value::Object(fields) => fields.values().for_each(|value| self.check_value(step, value)),
+            _ => {}
+        }
+    }
+
+    // Files can only be checked when their path does not depend on earlier
+    // steps or loop items.
+    fn check_local_file(&mut self, step: &str, path: &str) {
+        let resolved = substitute(path, |name| match name.strip_prefix("env.") {
+            Some(variable) => std::env::var(variable).ok(),
+            None => self.workflow.vars.get(name).cloned(),
+        });
+        if let Ok(resolved) = resolved {
+            if !Path::new(&resolved).exists() {
+                self.issue(Some(step), format!("Local file {} does not exist", resolved));
+            }
+        }
+    }
+
+    fn require(&mut self, step: &str, field: &str, value: &str) {
+        if value.trim().is_empty() {
+            self.issue(Some(step), format!("{} must not be empty", field));
+        }
+    }
+
+    // Steps of a phase refer to each other by plain name, while steps after
+    // the phase see them as `<phase>.<step>`.
+    fn check_phase(&mut self, phase: &str, steps: &[WorkflowStep]) {
+        let mark = self.known.len();
+        self.check_steps(steps, &format!("{}.", phase));
+        for name in &mut self.known[mark..] {
+            *name = format!("{}.{}", phase, name);
+        }
+    }
+
+    fn check_steps(&mut self, steps: &[WorkflowStep], prefix: &str) {
+        let mut seen: Vec<&str> = Vec::new();
+        for step in steps {
+            let name = format!("{}{}", prefix, step.name);
+            if step.name.trim().is_empty() {
+                self.issue(Some(&name), "Step has no name".to_string());
+            } else if seen.contains(&step.name.as_str()) {
+                self.issue(Some(&name), "Duplicate step name; conditions and ${steps.*} see only the latest".to_string());
+            }
+            seen.push(&step.name);
+
+            if let Some(condition) = &step.condition {
+                if !self.is_known(&condition.step) {
+                    self.issue(
+                        Some(&name),
+                        format!("Condition refers to step {:?}, which has not run by then", condition.step),
+                    );
+                }
+            }
+            if step.timeout_ms == Some(0) {
+                self.issue(Some(&name), "timeout_ms must be greater than 0".to_string());
+            }
+
+            match &step.action {
+                StepAction::Shell { command } => {
+                    self.require(&name, "command", command);
+                    self.check_text(&name, command);
+                }
+                StepAction::Adb { args } => {
+                    self.require(&name, "args", args);
+                    self.check_text(&name, args);
+                }
+                StepAction::Install { apk } => {
+                    self.require(&name, "apk", apk);
+                    self.check_text(&name, apk);
+                    self.check_local_file(&name, apk);
+                }
+                StepAction::Uninstall { package } => {
+                    self.require(&name, "package", package);
+                    self.check_text(&name, package);
+                }
+                StepAction::Push { local, remote } => {
+                    self.require(&name, "local", local);
+                    self.require(&name, "remote", remote);
+                    self.check_text(&name, local);
+                    self.check_text(&name, remote);
+                    self.check_local_file(&name, local);
+                }
+                StepAction::Pull { remote, local } => {
+                    self.require(&name, "remote", remote);
+                    self.require(&name, "local", local);
+                    self.check_text(&name, remote);
+                    self.check_text(&name, local);
+                }
+                StepAction::KeyEvent { keycode } => {
+                    self.require(&name, "keycode", keycode);
+                    self.check_text(&name, keycode);
+                }
+                StepAction::Tap { .. } | StepAction::Sleep { .. } => {}
+                StepAction::Parallel { commands, max_concurrency } => {
+                    if commands.is_empty() {
+                        self.issue(Some(&name), "Parallel step has no commands".to_string());
+                    }
+                    if *max_concurrency == Some(0) {
+                        self.issue(Some(&name), "max_concurrency must be greater than 0".to_string());
+                    }
+                    for command in commands {
+                        self.require(&name, "command", command);
+                        self.check_text(&name, command);
+                    }
+                }
+                StepAction::Custom { step_type, params } => {
+                    if !self.step_types.contains(&step_type.as_str()) {
+                        self.issue(Some(&name), format!("Unknown step type {:?}", step_type));
+                    }
+                    for value in params.values() {
+                        self.check_value(&name, value);
+                    }
+                }
+                StepAction::Loop { over, var, steps: body } => {
+                    self.require(&name, "as", var);
+                    match over {
+                        LoopItems::List(items) => {
+                            for item in items {
+                                self.check_text(&name, item);
+                            }
+                        }
+                        LoopItems::Text(text) => self.check_text(&name, text),
+                    }
+                    if body.is_empty() {
+                        self.issue(Some(&name), "Loop has no steps".to_string());
+                    }
+                    let mark = self.known.len();
+                    self.loop_vars.push(var.clone());
+                    self.check_steps(body, &format!("{}.", name));
+                    self.loop_vars.pop();
+                    self.known.truncate(mark);
+                }
+            }
+            self.known.push(step.name.clone());
+        }
+    }
+}
+
+impl Workflow {
+    // Checks everything that can be checked without a device: step
+    // parameters, placeholders, condition references and local files.
+    // Custom step types are reported as unknown; `WorkflowEngine::validate`
+    // accepts the ones it has handlers for.
+    pub fn validate(&self) -> ValidationReport {
+        self.validate_with(&[])
+    }
+
+    pub(crate) fn validate_with(&self, step_types: &[&str]) -> ValidationReport {
+        let mut validator = Validator {
+            workflow: self,
+            issues: Vec::new(),
+            known: Vec::new(),
+            loop_vars: Vec::new(),
+            step_types,
+        };
+        if self.steps.is_empty() {
+            validator.issue(None, "Workflow has no steps".to_string());
+        }
+        validator.check_phase("setup", &self.setup);
+        validator.check_steps(&self.steps, "");
+        validator.check_phase("on_failure", &self.on_failure);
+        validator.check_phase("teardown", &self.teardown);
+        ValidationReport {
+            workflow: self.name.clone(),
+            issues: validator.issues,
+        }
+    }
+}
+
+// Loads and validates a workflow file. A file that cannot be read or parsed
+// is an error rather than a report, since nothing in it can be checked.
+pub fn validate_workflow(path: impl AsRef<Path>) -> Result<ValidationReport, io::Error> {
+    Ok(Workflow::load(path)?.validate())
+}
+
+fn device_unavailable(device: &str) -> String {
+    format!("Device {} is not available", device.trim_start_matches("-s ").trim())
+}
+
+impl ADB {
+    // Validates the workflow and checks that `device` is online, without
+    // running any step.
+    pub fn execute_workflow_dry_run(&self, device: &str, workflow: &Workflow) -> Result<ValidationReport, io::Error> {
+        let mut report = workflow.validate();
+        if !self.is_online(device) {
+            report.issues.insert(0, ValidationIssue {
+                step: None,
+                message: device_unavailable(device),
+            });
+        }
+        Ok(report)
+    }
+
+    pub async fn execute_workflow_dry_run_async(&self, device: &str, workflow: &Workflow) -> Result<ValidationReport, io::Error> {
+        let mut report = workflow.validate();
+        if !self.is_online_async(device).await {
+            report.issues.insert(0, ValidationIssue {
+                step: None,
+                message: device_unavailable(device),
+            });
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,532 @@
+// src/wireless.rs
+
+use crate::ADB;
+use futures::{stream, Stream};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const DISCOVERY_POLL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct WirelessKeeperOptions {
+    pub check_interval: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for WirelessKeeperOptions {
+    fn default() -> Self {
+        WirelessKeeperOptions {
+            check_interval: Duration::from_secs(5),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WirelessEvent {
+    Dropped { address: String },
+    Reconnected { address: String, attempts: u32 },
+    ReconnectFailed { address: String, attempt: u32, retry_in: Duration, error: String },
+}
+
+// `adb connect` exits 0 even when it fails, so the message decides.
+fn check_connect_output(output: &str) -> Result<(), io::Error> {
+    if output.contains("connected to") && !output.contains("failed") {
+        Ok(())
+    } else {
+        Err(io::Error::other(output.trim().to_string()))
+    }
+}
+
+impl WirelessKeeperOptions {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LinkState {
+    connected: bool,
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+// Keeps `adb connect` sessions alive. Each check lists devices and pings the
+// ones that should be online; a device that is missing, offline or does not
+// answer is reconnected with exponential backoff.
+pub struct WirelessKeeper<'a> {
+    adb: &'a ADB,
+    options: WirelessKeeperOptions,
+    links: HashMap<String, LinkState>,
+}
+
+impl<'a> WirelessKeeper<'a> {
+    pub fn new<I, S>(adb: &'a ADB, addresses: I, options: WirelessKeeperOptions) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let now = Instant::now();
+        let links = addresses
+            .into_iter()
+            .map(|address| {
+                let state = LinkState {
+                    connected: true,
+                    attempts: 0,
+                    next_attempt: now,
+                };
+                (address.into(), state)
+            })
+            .collect();
+        WirelessKeeper { adb, options, links }
+    }
+
+    pub fn add(&mut self, address: &str) {
+        self.links.entry(address.to_string()).or_insert(LinkState {
+            connected: true,
+            attempts: 0,
+            next_attempt: Instant::now(),
+        });
+    }
+
+    pub fn remove(&mut self, address: &str) {
+        self.links.remove(address);
+    }
+
+    pub fn addresses(&self) -> Vec<String> {
+        self.links.keys().cloned().collect()
+    }
+
+    pub fn is_connected(&self, address: &str) -> bool {
+        self.links.get(address).is_some_and(|link| link.connected)
+    }
+
+    // Returns the addresses that should be (re)connected now, recording drops
+    // for links that were up until this check.
+    fn triage(&mut self, listing: &str, alive: impl Fn(&str) -> bool, events: &mut Vec<WirelessEvent>) -> Vec<String> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        for (address, link) in self.links.iter_mut() {
+            let listed = listing.lines().any(|line| {
+                let mut fields = line.split_whitespace();
+                fields.next() == Some(address.as_str()) && fields.next() == Some("device")
+            });
+            if link.connected && listed && alive(address) {
+                continue;
+            }
+            if link.connected {
+                link.connected = false;
+                link.attempts = 0;
+                link.next_attempt = now;
+                events.push(WirelessEvent::Dropped { address: address.clone() });
+            }
+            if link.next_attempt <= now {
+                due.push(address.clone());
+            }
+        }
+        due
+    }
+
+    fn record_attempt(&mut self, address: &str, result: Result<String, io::Error>, events: &mut Vec<WirelessEvent>) {
+        let Some(link) = self.links.get_mut(address) else {
+            return;
+        };
+        let result = result.and_then(|output| check_connect_output(&output));
+        link.attempts += 1;
+        let attempt = link.attempts;
+        let retry_in = self.options.backoff(attempt);
+        match result {
+            Ok(()) => {
+                link.connected = true;
+                events.push(WirelessEvent::Reconnected {
+                    address: address.to_string(),
+                    attempts: attempt,
+                });
+            }
+            Err(error) => {
+                link.next_attempt = Instant::now() + retry_in;
+                events.push(WirelessEvent::ReconnectFailed {
+                    address: address.to_string(),
+                    attempt,
+                    retry_in,
+                    error: error.to_string(),
+                });
+            }
+        }
+    }
+
+    pub fn check(&mut self) -> Result<Vec<WirelessEvent>, io::Error> {
+        let adb = self.adb;
+        let listing = adb.run_adb("devices")?;
+        let mut events = Vec::new();
+        let due = self.triage(
+            &listing,
+            |address| adb.run_adb(&format!("-s {} shell echo", address)).is_ok(),
+            &mut events,
+        );
+        for address in due {
+            // A stale entry blocks a fresh connect, so it is dropped first.
+            let _ = adb.run_adb(&format!("disconnect {}", address));
+            let result = adb.run_adb(&format!("connect {}", address));
+            self.record_attempt(&address, result, &mut events);
+        }
+        Ok(events)
+    }
+
+    pub async fn check_async(&mut self) -> Result<Vec<WirelessEvent>, io::Error> {
+        let adb = self.adb;
+        let listing = adb.run_adb_async("devices").await?;
+        let mut alive = HashMap::new();
+        for (address, _) in self.links.iter().filter(|(_, link)| link.connected) {
+            let ok = adb
+                .run_adb_async(&format!("-s {} shell echo", address))
+                .await
+                .is_ok();
+            alive.insert(address.clone(), ok);
+        }
+        let mut events = Vec::new();
+        let due = self.triage(&listing, |address| alive.get(address).copied().unwrap_or(false), &mut events);
+        for address in due {
+            let _ = adb.run_adb_async(&format!("disconnect {}", address)).await;
+            let result = adb.run_adb_async(&format!("connect {}", address)).await;
+            self.record_attempt(&address, result, &mut events);
+        }
+        Ok(events)
+    }
+
+    // Runs `check_async` every `check_interval` and yields each event as it
+    // happens. A failed device listing is yielded as an error and the keeper
+    // keeps going.
+    pub fn into_events(self) -> impl Stream<Item = Result<WirelessEvent, io::Error>> + 'a {
+        let interval = self.options.check_interval.max(Duration::from_millis(1));
+        stream::unfold((self, None, VecDeque::new()), move |(mut keeper, ticker, mut pending)| async move {
+            let mut ticker = ticker.unwrap_or_else(|| tokio::time::interval(interval));
+            loop {
+                if let Some(event) = pending.pop_front() {
+                    return Some((Ok(event), (keeper, Some(ticker), pending)));
+                }
+                ticker.tick().await;
+                match keeper.check_async().await {
+                    Ok(events) => pending.extend(events),
+                    Err(error) => return Some((Err(error), (keeper, Some(ticker), pending))),
+                }
+            }
+        })
+    }
+}
+
+impl ADB {
+    pub fn wireless_keeper<I, S>(&self, addresses: I, options: WirelessKeeperOptions) -> WirelessKeeper<'_>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        WirelessKeeper::new(self, addresses, options)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MdnsServiceKind {
+    // `_adb-tls-connect._tcp`: the Wireless Debugging connect port.
+    Connect,
+    // `_adb-tls-pairing._tcp`: only advertised while the pairing dialog is open.
+    Pairing,
+    // `_adb._tcp`: the legacy `adb tcpip` listener.
+    Legacy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsService {
+    pub instance: String,
+    pub kind: MdnsServiceKind,
+    pub address: String,
+}
+
+impl MdnsService {
+    pub fn host(&self) -> &str {
+        self.address.rsplit_once(':').map_or(self.address.as_str(), |(host, _)| host)
+    }
+
+    // `adb mdns services` prints one tab-separated service per line:
+    // `adb-1A2B3C-xYz12\t_adb-tls-connect._tcp\t192.168.1.20:37123`
+    fn parse_list(output: &str) -> Vec<Self> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let instance = fields.next()?.to_string();
+                let kind = match fields.next()?.trim_end_matches('.') {
+                    "_adb-tls-connect._tcp" => MdnsServiceKind::Connect,
+                    "_adb-tls-pairing._tcp" => MdnsServiceKind::Pairing,
+                    "_adb._tcp" => MdnsServiceKind::Legacy,
+                    _ => return None,
+                };
+                let address = fields.next()?.to_string();
+                Some(MdnsService { instance, kind, address })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WirelessDebugOptions {
+    // The mDNS instance name of the device, e.g. from a saved pairing.
+    // Without it the only advertised device (or the one just paired) is used.
+    pub instance: Option<String>,
+    // The six-digit code from "Pair device with pairing code".
+    pub pairing_code: Option<String>,
+    // The `ip:port` shown next to the pairing code; discovered over mDNS
+    // when unset.
+    pub pairing_address: Option<String>,
+    pub timeout: Duration,
+}
+
+impl Default for WirelessDebugOptions {
+    fn default() -> Self {
+        WirelessDebugOptions {
+            instance: None,
+            pairing_code: None,
+            pairing_address: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+// What is needed to find a paired device again. The connect port changes on
+// every reboot and toggle, but the mDNS instance name does not, and the
+// pairing key itself is kept by the adb server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WirelessPairing {
+    pub instance: String,
+    pub address: String,
+}
+
+impl WirelessPairing {
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        fs::write(path, format!("instance={}\naddress={}\n", self.instance, self.address))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        let field = |name: &str| {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+                .map(|value| value.trim().to_string())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Pairing file has no {}", name)))
+        };
+        Ok(WirelessPairing {
+            instance: field("instance")?,
+            address: field("address")?,
+        })
+    }
+}
+
+fn check_pair_output(output: &str) -> Result<(), io::Error> {
+    if output.contains("Successfully paired") {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Pairing failed: {}", output.trim()),
+        ))
+    }
+}
+
+fn find_service<'s>(
+    services: &'s [MdnsService],
+    kind: MdnsServiceKind,
+    instance: Option<&str>,
+    host: Option<&str>,
+) -> Option<&'s MdnsService> {
+    let mut candidates = services.iter().filter(|service| {
+        service.kind == kind
+            && instance.is_none_or(|instance| service.instance == instance)
+            && host.is_none_or(|host| service.host() == host)
+    });
+    let first = candidates.next()?;
+    // Several devices advertising at once is ambiguous without a filter.
+    if instance.is_none() && host.is_none() && candidates.next().is_some() {
+        return None;
+    }
+    Some(first)
+}
+
+fn not_discovered(kind: MdnsServiceKind, timeout: Duration) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!(
+            "No unique {:?} mDNS service found within {:?}; pass an instance name if several devices advertise",
+            kind, timeout
+        ),
+    )
+}
+
+impl ADB {
+    pub fn discover_mdns_services(&self) -> Result<Vec<MdnsService>, io::Error> {
+        Ok(MdnsService::parse_list(&self.run_adb("mdns services")?))
+    }
+
+    pub async fn discover_mdns_services_async(&self) -> Result<Vec<MdnsService>, io::Error> {
+        Ok(MdnsService::parse_list(&self.run_adb_async("mdns services").await?))
+    }
+
+    fn wait_for_service(
+        &self,
+        kind: MdnsServiceKind,
+        instance: Option<&str>,
+        host: Option<&str>,
+        timeout: Duration,
+    ) -> Result<MdnsService, io::Error> {
+        let start = Instant::now();
+        loop {
+            let services = self.discover_mdns_services()?;
+            if let Some(service) = find_service(&services, kind, instance, host) {
+                return Ok(service.clone());
+            }
+            if start.elapsed() >= timeout {
+                return Err(not_discovered(kind, timeout));
+            }
+            std::thread::sleep(DISCOVERY_POLL);
+        }
+    }
+
+    async fn wait_for_service_async(
+        &self,
+        kind: MdnsServiceKind,
+        instance: Option<&str>,
+        host: Option<&str>,
+        timeout: Duration,
+    ) -> Result<MdnsService, io::Error> {
+        let start = Instant::now();
+        loop {
+            let services = self.discover_mdns_services_async().await?;
+            if let Some(service) = find_service(&services, kind, instance, host) {
+                return Ok(service.clone());
+            }
+            if start.elapsed() >= timeout {
+                return Err(not_discovered(kind, timeout));
+            }
+            tokio::time::sleep(DISCOVERY_POLL).await;
+        }
+    }
+
+    pub fn pair(&self, address: &str, code: &str) -> Result<(), io::Error> {
+        check_pair_output(&self.run_adb(&format!("pair {} {}", address, code))?)
+    }
+
+    pub async fn pair_async(&self, address: &str, code: &str) -> Result<(), io::Error> {
+        check_pair_output(&self.run_adb_async(&format!("pair {} {}", address, code)).await?)
+    }
+
+    pub fn connect(&self, address: &str) -> Result<(), io::Error> {
+        check_connect_output(&self.run_adb(&format!("connect {}", address))?)
+    }
+
+    pub async fn connect_async(&self, address: &str) -> Result<(), io::Error> {
+        check_connect_output(&self.run_adb_async(&format!("connect {}", address)).await?)
+    }
+
+    // Turns on Wireless Debugging from an existing (usually USB) connection.
+    // The device still asks the user to allow the current network once.
+    pub fn enable_wireless_debugging(&self, device: &str) -> Result<(), io::Error> {
+        self.require_api(device, 30)?;
+        self.run_adb(&format!("{} shell settings put global adb_wifi_enabled 1", device))?;
+        Ok(())
+    }
+
+    pub async fn enable_wireless_debugging_async(&self, device: &str) -> Result<(), io::Error> {
+        self.require_api_async(device, 30).await?;
+        self.run_adb_async(&format!("{} shell settings put global adb_wifi_enabled 1", device))
+            .await?;
+        Ok(())
+    }
+
+    // Pairs first when a code is given, then finds the device's dynamic
+    // connect port over mDNS and connects to it. The returned pairing can be
+    // saved and handed to `reconnect_wireless` after a reboot.
+    pub fn connect_wireless(&self, options: &WirelessDebugOptions) -> Result<WirelessPairing, io::Error> {
+        let start = Instant::now();
+        let mut paired_host = None;
+        if let Some(code) = &options.pairing_code {
+            let address = match &options.pairing_address {
+                Some(address) => address.clone(),
+                None => {
+                    self.wait_for_service(MdnsServiceKind::Pairing, None, None, options.timeout)?
+                        .address
+                }
+            };
+            self.pair(&address, code)?;
+            paired_host = address.rsplit_once(':').map(|(host, _)| host.to_string());
+        }
+
+        let remaining = options.timeout.saturating_sub(start.elapsed());
+        let service = self.wait_for_service(
+            MdnsServiceKind::Connect,
+            options.instance.as_deref(),
+            paired_host.as_deref(),
+            remaining,
+        )?;
+        self.connect(&service.address)?;
+        Ok(WirelessPairing {
+            instance: service.instance,
+            address: service.address,
+        })
+    }
+
+    pub async fn connect_wireless_async(&self, options: &WirelessDebugOptions) -> Result<WirelessPairing, io::Error> {
+        let start = Instant::now();
+        let mut paired_host = None;
+        if let Some(code) = &options.pairing_code {
+            let address = match &options.pairing_address {
+                Some(address) => address.clone(),
+                None => {
+                    self.wait_for_service_async(MdnsServiceKind::Pairing, None, None, options.timeout)
+                        .await?
+                        .address
+                }
+            };
+            self.pair_async(&address, code).await?;
+            paired_host = address.rsplit_once(':').map(|(host, _)| host.to_string());
+        }
+
+        let remaining = options.timeout.saturating_sub(start.elapsed());
+        let service = self
+            .wait_for_service_async(
+                MdnsServiceKind::Connect,
+                options.instance.as_deref(),
+                paired_host.as_deref(),
+                remaining,
+            )
+            .await?;
+        self.connect_async(&service.address).await?;
+        Ok(WirelessPairing {
+            instance: service.instance,
+            address: service.address,
+        })
+    }
+
+    // Looks the device up by its instance name so a changed port is picked
+    // up, and updates the pairing with the address it connected to.
+    pub fn reconnect_wireless(&self, pairing: &mut WirelessPairing, timeout: Duration) -> Result<(), io::Error> {
+        let service = self.wait_for_service(MdnsServiceKind::Connect, Some(&pairing.instance), None, timeout)?;
+        self.connect(&service.address)?;
+        pairing.address = service.address;
+        Ok(())
+    }
+
+    pub async fn reconnect_wireless_async(&self, pairing: &mut WirelessPairing, timeout: Duration) -> Result<(), io::Error> {
+        let service = self
+            .wait_for_service_async(MdnsServiceKind::Connect, Some(&pairing.instance), None, timeout)
+            .await?;
+        self.connect_async(&service.address).await?;
+        pairing.address = service.address;
+        Ok(())
+    }
+}